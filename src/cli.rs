@@ -0,0 +1,103 @@
+use crate::event::MsgFormat;
+use crate::locks::LockProtocol;
+use crate::policy;
+use crate::sink::SinkSpec;
+use crate::workload::parse_duration;
+use crate::workload::parse_scale;
+use clap::builder::PossibleValuesParser;
+use clap::builder::RangedU64ValueParser;
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Simulate the OS processes scheduling.
+#[derive(Parser)]
+#[command(name = "simulate_schedule", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the workload once.
+    Run(RunArgs),
+    /// Run the same workload, with the same seed, under several policies in
+    /// virtual time, and compare their reports.
+    Compare {
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_values_t = policy::NAMES.map(String::from),
+            value_parser = PossibleValuesParser::new(policy::NAMES),
+        )]
+        policies: Vec<String>,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Replay a workload or trace file (TOML, JSON, SWF or CSV) in virtual time.
+    Replay {
+        file: PathBuf,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Summarize the CSV files written by `--report`.
+    Report {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Args, Clone)]
+pub struct RunArgs {
+    /// Where the messages of the processes go: none, stdout, file:<path>
+    /// (rotated at 1 MiB), tcp:<addr> of an echo server or tcp-len:<addr>.
+    #[arg(long, default_value = "none")]
+    pub msg: SinkSpec,
+    #[arg(long, value_enum, default_value = "text")]
+    pub msg_format: MsgFormat,
+    /// Worker threads, or simulated CPUs, one per CPU by default.
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub threads: Option<usize>,
+    #[arg(long, default_value = "priority", value_parser = PossibleValuesParser::new(policy::NAMES))]
+    pub policy: String,
+    #[arg(long, default_value = "20ms", value_parser = parse_duration)]
+    pub time_slice: Duration,
+    /// How a simulated mutex lends priority to its holder.
+    #[arg(long, value_enum, default_value = "none")]
+    pub lock_protocol: LockProtocol,
+    /// Cut bursts longer than the time slice.
+    #[arg(long)]
+    pub preempt: bool,
+    /// Run in virtual time, as a discrete-event simulation.
+    #[arg(long = "virtual")]
+    pub virtual_time: bool,
+    /// The seed of every random choice, a random one by default.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Run the processes of this file instead of the built-in ones.
+    #[arg(long)]
+    pub workload: Option<PathBuf>,
+    /// Multiply every arrival time and burst of the workload.
+    #[arg(long, value_parser = parse_scale)]
+    pub time_scale: Option<f64>,
+    /// How many random processes to run, 20 by default, or as the workload says.
+    #[arg(long)]
+    pub random: Option<usize>,
+    #[arg(long, default_value = "200ms", value_parser = parse_duration)]
+    pub print_interval: Duration,
+    /// Do not print the processes while they run, nor the report.
+    #[arg(long)]
+    pub quiet: bool,
+    /// Write the per-process report as CSV.
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+    /// Write a Gantt chart, as HTML if the file ends with `.html`, SVG otherwise.
+    #[arg(long)]
+    pub gantt: Option<PathBuf>,
+    /// Write a Chrome Trace Event file.
+    #[arg(long)]
+    pub trace: Option<PathBuf>,
+}
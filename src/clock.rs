@@ -0,0 +1,87 @@
+use crate::util::time_test;
+use std::cell::Cell;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The time every process sees. In real mode it is the wall clock, in
+/// virtual mode it only moves when the simulation advances it, and `burn`
+/// records CPU demand instead of sleeping.
+static VIRTUAL: AtomicBool = AtomicBool::new(false);
+static VIRTUAL_NOW: AtomicU64 = AtomicU64::new(0);
+static START_POINT: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    static DEMAND: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+    /// What is left of the time slice of the running poll, `None` if not preemptive.
+    static BUDGET: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// The demand which did not fit in the budget.
+    static OVERFLOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Also restarts the virtual clock, for a new simulation.
+pub fn set_virtual(on: bool) {
+    VIRTUAL.store(on, Ordering::Relaxed);
+    VIRTUAL_NOW.store(0, Ordering::Relaxed);
+}
+
+pub fn is_virtual() -> bool {
+    VIRTUAL.load(Ordering::Relaxed)
+}
+
+/// The time since the simulation started.
+pub fn now() -> Duration {
+    if is_virtual() {
+        Duration::from_nanos(VIRTUAL_NOW.load(Ordering::Relaxed))
+    } else {
+        START_POINT.get_or_init(Instant::now).elapsed()
+    }
+}
+
+/// Move the virtual clock forward, it never goes back.
+pub fn advance_to(t: Duration) {
+    VIRTUAL_NOW.fetch_max(t.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Do `d` of "heavy work" on the CPU. Under a budget only what fits is
+/// done now, the rest is left for the scheduler to run later.
+pub fn burn(mut d: Duration) {
+    if let Some(budget) = BUDGET.with(Cell::get) {
+        let run = d.min(budget);
+
+        BUDGET.with(|b| b.set(Some(budget - run)));
+        OVERFLOW.with(|o| o.set(o.get() + (d - run)));
+        d = run;
+    }
+
+    if is_virtual() {
+        DEMAND.with(|demand| demand.set(demand.get() + d));
+    } else {
+        thread::sleep(d);
+    }
+}
+
+/// Run `f` with a budget of CPU time, returning what did not fit in it.
+pub fn with_budget<R>(budget: Option<Duration>, f: impl FnOnce() -> R) -> (Duration, R) {
+    BUDGET.with(|b| b.set(budget));
+    OVERFLOW.with(|o| o.set(Duration::ZERO));
+    let r = f();
+    BUDGET.with(|b| b.set(None));
+
+    (OVERFLOW.with(Cell::take), r)
+}
+
+/// Run `f` and return the CPU time it used.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (Duration, R) {
+    if is_virtual() {
+        DEMAND.with(|demand| demand.set(Duration::ZERO));
+        let r = f();
+        (DEMAND.with(|demand| demand.take()), r)
+    } else {
+        time_test(f)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::clock;
+use clap::ValueEnum;
+use crossbeam_channel::Sender;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::time::Duration;
+
+/// What the processes and the kernel tell the message sink.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SimEvent {
+    PriorityChanged {
+        op: PriorityOp,
+        by: i32,
+        priority: i32,
+    },
+    TicketsTransferred {
+        to: u32,
+        tickets: u32,
+    },
+    /// A real-time process failed the admission test of the policy.
+    Rejected {
+        utilization: f64,
+    },
+    FileRead {
+        content: String,
+    },
+    FileReadErr {
+        err: String,
+    },
+    FileWriteOk {
+        path: String,
+    },
+    FileWriteErr {
+        path: String,
+        err: String,
+    },
+    Said {
+        text: String,
+    },
+    /// What `Op::Recv` got.
+    Received {
+        from: u32,
+        payload: String,
+    },
+    /// A child reaped by `Op::Wait` or `Op::WaitAny`.
+    Reaped {
+        child: u32,
+        code: i32,
+    },
+    /// An op which could not be done, as a spawn, wait, send or lock.
+    OpErr {
+        err: String,
+    },
+    Finished {
+        code: i32,
+        cpu_us: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityOp {
+    Add,
+    Sub,
+    Set,
+}
+
+/// A `SimEvent` with who sent it, on which worker and when.
+#[derive(Debug, Clone)]
+pub struct Msg {
+    pub at: Duration,
+    pub pid: u32,
+    pub worker: Option<usize>,
+    pub name: String,
+    pub event: SimEvent,
+}
+
+/// How the messages are written out.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MsgFormat {
+    /// One human-readable line each.
+    Text,
+    /// JSON Lines.
+    Json,
+}
+
+/// The sending end a process polls with, stamping what it sends.
+#[derive(Clone)]
+pub struct MsgTx {
+    tx: Sender<Msg>,
+    pid: u32,
+    worker: Option<usize>,
+}
+
+impl MsgTx {
+    pub fn new(tx: Sender<Msg>, pid: u32, worker: Option<usize>) -> Self {
+        Self { tx, pid, worker }
+    }
+
+    pub fn send(&self, name: &str, event: SimEvent) {
+        let _ = self.tx.send(Msg {
+            at: clock::now(),
+            pid: self.pid,
+            worker: self.worker,
+            name: name.to_string(),
+            event,
+        });
+    }
+}
+
+impl Msg {
+    pub fn render(&self, format: MsgFormat) -> String {
+        match format {
+            MsgFormat::Text => self.to_string(),
+            MsgFormat::Json => {
+                let mut value = json!({
+                    "at_us": self.at.as_micros() as u64,
+                    "pid": self.pid,
+                    "worker": self.worker,
+                    "name": self.name,
+                });
+                if let Ok(serde_json::Value::Object(event)) = serde_json::to_value(&self.event) {
+                    value.as_object_mut().unwrap().extend(event);
+                }
+                value.to_string()
+            }
+        }
+    }
+}
+
+/// The messages as they always read.
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = &self.name;
+
+        match &self.event {
+            SimEvent::PriorityChanged { op, by, .. } => {
+                let op = match op {
+                    PriorityOp::Add => "ADD",
+                    PriorityOp::Sub => "SUB",
+                    PriorityOp::Set => "SET",
+                };
+                write!(f, "{} {} PRIORITY => {}", name, op, by)
+            }
+            SimEvent::TicketsTransferred { to, tickets } => {
+                write!(f, "{} TRANSFER {} TICKETS => {}", name, tickets, to)
+            }
+            SimEvent::Rejected { utilization } => write!(
+                f,
+                "{} REJECTED BY ADMISSION TEST: U = {:.2}",
+                name, utilization
+            ),
+            SimEvent::FileRead { content } => write!(f, "{} READ: {}", name, content),
+            SimEvent::FileReadErr { err } => write!(f, "{} READ ERR: {}", name, err),
+            SimEvent::FileWriteOk { path } => write!(f, "{} [{}] WRITE OK.", name, path),
+            SimEvent::FileWriteErr { path, err } => {
+                write!(f, "{} [{}] WRITE ERR: {}", name, path, err)
+            }
+            SimEvent::Said { text } => write!(f, "{} say: {}", name, text),
+            SimEvent::Received { from, payload } => {
+                write!(f, "{} RECV FROM {}: {}", name, from, payload)
+            }
+            SimEvent::Reaped { child, code } => write!(f, "{} REAPED {} ({})", name, child, code),
+            SimEvent::OpErr { err } => write!(f, "{} OP ERR: {}", name, err),
+            SimEvent::Finished { code, cpu_us } => write!(
+                f,
+                "{} FINISHED ({}) after {:?} on CPU",
+                name,
+                code,
+                Duration::from_micros(*cpu_us)
+            ),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::ops::OpsRes;
+use crate::pcb::Pcb;
+use crate::pcb::ProcState;
+use crate::scheduler::Share;
+use crate::util::convert::make_mut;
+use std::collections::HashMap;
+
+/// The children of every process, and the parents blocked waiting for one.
+#[derive(Default)]
+pub struct Family {
+    /// Children not reaped yet, by parent.
+    children: HashMap<u32, Vec<u32>>,
+    /// Finished children not reaped yet, by parent, first finished first.
+    zombies: HashMap<u32, Vec<u32>>,
+    /// Parents in `Op::Wait`, or `Op::WaitAny` for `None`.
+    waiting: HashMap<u32, (Pcb, Option<u32>)>,
+}
+
+/// What a wait of a parent comes to.
+pub enum Reap {
+    /// The child with its exit code, now `ProcState::Terminated`.
+    Exited(u32, i32),
+    /// No such child.
+    NoChild,
+    /// The child is still running.
+    Pending,
+}
+
+impl Family {
+    pub fn add_child(&mut self, parent: u32, child: u32) {
+        self.children.entry(parent).or_default().push(child);
+    }
+
+    /// Reap `child` of `parent`, or any of its children for `None`.
+    pub fn reap(&mut self, parent: u32, child: Option<u32>, share: &Share) -> Reap {
+        let Some(children) = self.children.get_mut(&parent) else {
+            return Reap::NoChild;
+        };
+        let zombies = self.zombies.entry(parent).or_default();
+        let zombie = match child {
+            Some(child) if !children.contains(&child) => return Reap::NoChild,
+            Some(child) => zombies.iter().position(|&z| z == child),
+            None if children.is_empty() => return Reap::NoChild,
+            None => (!zombies.is_empty()).then_some(0),
+        };
+        let Some(i) = zombie else {
+            return Reap::Pending;
+        };
+        let child = zombies.remove(i);
+
+        children.retain(|&c| c != child);
+        let pinfo = share.pinfo(child).unwrap();
+        unsafe { make_mut(pinfo) }.set_state(ProcState::Terminated);
+        Reap::Exited(child, pinfo.exit_code)
+    }
+
+    /// Block `pcb` until the child it waits for finishes, unless it already
+    /// did: then `pcb` is back with the result.
+    pub fn wait(&mut self, pcb: Pcb, child: Option<u32>, share: &Share) -> Option<Pcb> {
+        let id = pcb.pinfo.id;
+
+        match self.reap(id, child, share) {
+            Reap::Pending => {
+                self.waiting.insert(id, (pcb, child));
+                None
+            }
+            reap => Some(pcb.with_reap(reap)),
+        }
+    }
+
+    /// `pcb` became a zombie: wake its parent if it waits for it, and reap it
+    /// right away if nobody ever will. Its own zombies are reaped too.
+    pub fn exit(&mut self, pcb: &Pcb, share: &Share) -> Option<Pcb> {
+        let id = pcb.pinfo.id;
+
+        // orphans are reaped by nobody, as if by init
+        for zombie in self.zombies.remove(&id).unwrap_or_default() {
+            let pinfo = share.pinfo(zombie).unwrap();
+            unsafe { make_mut(pinfo) }.set_state(ProcState::Terminated);
+        }
+        self.children.remove(&id);
+
+        let parent = pcb.pinfo.parent.filter(|p| self.children.contains_key(p));
+        let Some(parent) = parent else {
+            pcb.set_state(ProcState::Terminated);
+            return None;
+        };
+        self.zombies.entry(parent).or_default().push(id);
+
+        // whatever it waits for, see again whether it is still pending
+        let &(_, child) = self.waiting.get(&parent)?;
+        match self.reap(parent, child, share) {
+            Reap::Pending => None,
+            reap => {
+                let (waiter, _) = self.waiting.remove(&parent).unwrap();
+                Some(waiter.with_reap(reap))
+            }
+        }
+    }
+
+    pub fn blocked(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Take every waiting parent, with what it waits for.
+    pub fn drain(&mut self) -> Vec<(Pcb, String)> {
+        self.waiting
+            .drain()
+            .map(|(_, (pcb, child))| match child {
+                Some(child) => (pcb, format!("child {}", child)),
+                None => (pcb, "any child".to_string()),
+            })
+            .collect()
+    }
+}
+
+impl Pcb {
+    /// Hand the result of a wait to the process.
+    pub fn with_reap(mut self, reap: Reap) -> Self {
+        self.ops_res = match reap {
+            Reap::Exited(child, code) => OpsRes::Exited { child, code },
+            Reap::NoChild => OpsRes::NoChild,
+            Reap::Pending => unreachable!("a pending wait blocks"),
+        };
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::tests::simulate;
+    use std::time::Duration;
+
+    #[test]
+    fn wait_any_reaps_every_child_then_none() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "parent"
+            steps = [
+                { spawn = { name = "a", steps = [{ burst = "2ms" }, { exit = 1 }] } },
+                { spawn = { name = "b", steps = [{ burst = "4ms" }, { exit = 2 }] } },
+                "wait_any", "wait_any", "wait_any",
+                { burst = "1ms" },
+            ]
+            "#,
+        );
+
+        assert!(report.blocked.is_empty());
+        let finish = |name| {
+            let p = report.processes.iter().find(|p| p.name == name).unwrap();
+            p.finish.unwrap()
+        };
+        assert_eq!(finish("a"), Duration::from_millis(2));
+        assert_eq!(finish("b"), Duration::from_millis(4));
+        assert_eq!(finish("parent"), Duration::from_millis(5));
+        let codes: Vec<_> = report.processes.iter().map(|p| p.exit_code).collect();
+        assert_eq!(codes, [0, 1, 2]);
+    }
+
+    #[test]
+    fn wait_for_a_zombie_returns_at_once() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "parent"
+            steps = [
+                { spawn = { name = "child", steps = [{ burst = "1ms" }] } },
+                { burst = "3ms" },
+                "wait",
+            ]
+            "#,
+        );
+
+        assert!(report.blocked.is_empty());
+        assert_eq!(report.processes[0].finish, Some(Duration::from_millis(3)));
+        assert_eq!(report.processes[1].finish, Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn wait_without_child_does_not_block() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "childless"
+            steps = ["wait_any", { burst = "1ms" }]
+            "#,
+        );
+
+        assert!(report.blocked.is_empty());
+        assert_eq!(report.processes[0].finish, Some(Duration::from_millis(1)));
+    }
+}
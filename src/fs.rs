@@ -1,86 +1,117 @@
+use crate::clock;
 use crate::ops::FileReadRes;
 use crate::ops::FileWriteRes;
 use crate::ops::OpsRes;
 use crate::ops::OpsType;
 use crate::ops::Stone;
 use crate::pcb::Pcb;
+use crate::pcb::ProcState;
+use crate::rng::Rng;
+use crate::scheduler::Share;
+use crate::timeline::SpanEnd;
+use crate::timeline::Track;
+use crate::util::convert::make_mut;
+use crate::util::types::SStr;
+use crate::util::types::SS;
 use crossbeam::sync::Unparker;
 use crossbeam_channel::Receiver;
+use parking_lot::Mutex;
 use std::fs::read_dir;
 use std::fs::File;
 use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
 use work_queue::Queue;
-use wutil::convert::make_mut;
-use wutil::random::gen;
-use wutil::types::SPath;
-use wutil::types::SStr;
-use wutil::types::SS;
 
-pub static mut TXTS: Vec<PathBuf> = Vec::new();
+pub static TXTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
 
 pub enum FileOp {
     Read(SS),
     Write { path: String, content: SStr },
 }
 
+impl FileOp {
+    pub fn span_end(&self) -> SpanEnd {
+        match self {
+            FileOp::Read(_) => SpanEnd::FileRead,
+            FileOp::Write { .. } => SpanEnd::FileWrite,
+        }
+    }
+}
+
 pub fn init_txts() {
+    let mut txts = TXTS.lock();
+    txts.clear();
+
     for entry in read_dir("./txt").unwrap() {
-        unsafe {
-            TXTS.push(entry.unwrap().path());
-        }
+        txts.push(entry.unwrap().path());
     }
+    // read_dir order depends on the file system, keep runs reproducible
+    txts.sort();
 }
 
 pub fn fs_run(
+    id: usize,
     rx: Receiver<(Pcb, FileOp)>,
     ready_queue: &'static Queue<Pcb>,
-    fs_unparkers: &'static Vec<Unparker>,
+    share: &'static Share,
+    fs_unparkers: &'static [Unparker],
+    mut rng: Rng,
 ) {
-    for (mut pcb, file_op) in rx {
-        let stone = match file_op {
-            FileOp::Read(p) => {
-                let file = File::open(p);
+    for (mut pcb, op) in rx {
+        let start = clock::now();
+        let reason = op.span_end();
+        file_op(&mut pcb, op);
+        share
+            .timeline
+            .record(Track::Fs(id), pcb.pinfo.id, start, clock::now(), reason);
+        pcb.set_state(ProcState::Ready);
+        ready_queue.push(pcb);
 
-                pcb.ops_res = match pcb.p.file_buf() {
-                    Some(buf) => match file {
-                        Ok(mut f) => {
-                            buf.clear();
+        let maybe_awaken = rng.gen(0..fs_unparkers.len());
+        unsafe {
+            fs_unparkers.get_unchecked(maybe_awaken).unpark();
+        }
+    }
+}
 
-                            match f.read_to_string(buf) {
-                                Ok(n) => OpsRes::FileReadRes(FileReadRes::Ok(n)),
-                                Err(e) => OpsRes::FileReadRes(FileReadRes::Err(e)),
-                            }
-                        }
-                        Err(e) => OpsRes::FileReadRes(FileReadRes::Err(e)),
-                    },
-                    None => OpsRes::FileReadRes(FileReadRes::FileBufReturnNone),
-                };
+/// Do the file op for `pcb`, leaving the result in its `ops_res`.
+pub fn file_op(pcb: &mut Pcb, file_op: FileOp) {
+    let stone = match file_op {
+        FileOp::Read(p) => {
+            let file = File::open(p);
 
-                Stone::Ops(OpsType::File)
-            }
-            FileOp::Write { path, content } => {
-                let file = File::create(&path);
+            pcb.ops_res = match pcb.p.file_buf() {
+                Some(buf) => match file {
+                    Ok(mut f) => {
+                        buf.clear();
 
-                pcb.ops_res = match file {
-                    Ok(mut f) => match f.write_all(content.as_bytes()) {
-                        Ok(()) => OpsRes::FileWriteRes(FileWriteRes::Ok { path }),
-                        Err(e) => OpsRes::FileWriteRes(FileWriteRes::Err { path, err: e }),
-                    },
-                    Err(e) => OpsRes::FileWriteRes(FileWriteRes::Err { path, err: e }),
-                };
+                        match f.read_to_string(buf) {
+                            Ok(_) => OpsRes::FileReadRes(FileReadRes::Ok),
+                            Err(e) => OpsRes::FileReadRes(FileReadRes::Err(e)),
+                        }
+                    }
+                    Err(e) => OpsRes::FileReadRes(FileReadRes::Err(e)),
+                },
+                None => OpsRes::FileReadRes(FileReadRes::FileBufReturnNone),
+            };
 
-                Stone::Ops(OpsType::File)
-            }
-        };
+            Stone::Ops(OpsType::File)
+        }
+        FileOp::Write { path, content } => {
+            let file = File::create(&path);
 
-        unsafe { make_mut(pcb.pinfo) }.stones.push_back(stone);
-        ready_queue.push(pcb);
+            pcb.ops_res = match file {
+                Ok(mut f) => match f.write_all(content.as_bytes()) {
+                    Ok(()) => OpsRes::FileWriteRes(FileWriteRes::Ok { path }),
+                    Err(e) => OpsRes::FileWriteRes(FileWriteRes::Err { path, err: e }),
+                },
+                Err(e) => OpsRes::FileWriteRes(FileWriteRes::Err { path, err: e }),
+            };
 
-        let maybe_awaken = gen(0..fs_unparkers.len());
-        unsafe {
-            fs_unparkers.get_unchecked(maybe_awaken).unpark();
+            Stone::Ops(OpsType::File)
         }
-    }
+    };
+
+    unsafe { make_mut(pcb.pinfo) }.stones.push_back(stone);
 }
@@ -0,0 +1,205 @@
+use crate::scheduler::Share;
+use crate::timeline::Span;
+use crate::timeline::SpanEnd;
+use crate::timeline::Track;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+const WIDTH: f64 = 1200.0;
+const LABEL_WIDTH: f64 = 140.0;
+const ROW_HEIGHT: f64 = 20.0;
+const HEADER_HEIGHT: f64 = 24.0;
+
+/// Write the chart of `share.timeline` to `path`, as a standalone HTML page
+/// when it ends with `.html`, as SVG otherwise.
+pub fn export(share: &Share, path: &Path) -> io::Result<()> {
+    let chart = match path.extension().and_then(|e| e.to_str()) {
+        Some("html" | "htm") => html(share),
+        _ => svg(share),
+    };
+
+    fs::write(path, chart)
+}
+
+pub fn html(share: &Share) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Schedule</title>\n\
+         <style>\nbody {{ font-family: sans-serif; }}\n\
+         rect.span:hover {{ stroke: #000; stroke-width: 1.5; }}\n</style>\n</head>\n\
+         <body>\n{}</body>\n</html>\n",
+        svg(share)
+    )
+}
+
+/// A Gantt chart with a row per worker and file system thread, colored by
+/// process, then a row per process, colored by where it ran. Every span has
+/// a tooltip.
+pub fn svg(share: &Share) -> String {
+    let spans = share.timeline.spans();
+    let tracks: BTreeSet<Track> = spans.iter().map(|s| s.track).collect();
+    let pids: BTreeSet<u32> = spans.iter().map(|s| s.pid).collect();
+    let start = spans.iter().map(|s| s.start).min().unwrap_or_default();
+    let end = spans.iter().map(|s| s.end).max().unwrap_or_default();
+    let scale = WIDTH / (end - start).as_secs_f64().max(f64::EPSILON);
+    let x = |t: Duration| LABEL_WIDTH + (t - start).as_secs_f64() * scale;
+    let name = |pid: u32| {
+        share
+            .pinfo(pid)
+            .and_then(|p| p.name)
+            .map_or_else(|| format!("#{}", pid), |n| escape(n))
+    };
+
+    let rows = tracks.len() + pids.len();
+    let height = 2.0 * HEADER_HEIGHT + rows as f64 * ROW_HEIGHT + ROW_HEIGHT;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">",
+        LABEL_WIDTH + WIDTH + 20.0,
+        height
+    )
+    .unwrap();
+
+    let mut y = 0.0;
+    let section = |out: &mut String, y: &mut f64, title: &str| {
+        writeln!(
+            out,
+            "<text x=\"4\" y=\"{}\" font-weight=\"bold\">{}</text>",
+            *y + 16.0,
+            title
+        )
+        .unwrap();
+        *y += HEADER_HEIGHT;
+    };
+    let rect = |out: &mut String, y: f64, span: &Span, fill: String| {
+        writeln!(
+            out,
+            "<rect class=\"span\" x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"><title>{} on {}\n{:.1?} .. {:.1?} ({:.1?})\nend: {}</title></rect>",
+            x(span.start),
+            y + 2.0,
+            (x(span.end) - x(span.start)).max(0.5),
+            ROW_HEIGHT - 4.0,
+            fill,
+            name(span.pid),
+            track_name(span.track),
+            span.start - start,
+            span.end - start,
+            span.end - span.start,
+            span.reason.as_str(),
+        )
+        .unwrap();
+    };
+    let label = |out: &mut String, y: f64, text: &str| {
+        writeln!(
+            out,
+            "<text x=\"4\" y=\"{}\">{}</text>",
+            y + ROW_HEIGHT - 6.0,
+            text
+        )
+        .unwrap();
+    };
+
+    section(&mut out, &mut y, "Workers");
+    for track in &tracks {
+        label(&mut out, y, &track_name(*track));
+        for span in spans.iter().filter(|s| s.track == *track) {
+            rect(&mut out, y, span, process_color(span.pid));
+        }
+        y += ROW_HEIGHT;
+    }
+
+    section(&mut out, &mut y, "Processes");
+    for pid in &pids {
+        label(&mut out, y, &name(*pid));
+        for span in spans.iter().filter(|s| s.pid == *pid) {
+            rect(&mut out, y, span, reason_color(span.reason).to_string());
+        }
+        y += ROW_HEIGHT;
+    }
+
+    writeln!(
+        out,
+        "<text x=\"{}\" y=\"{}\">0</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.1?}</text>",
+        LABEL_WIDTH,
+        y + 14.0,
+        LABEL_WIDTH + WIDTH,
+        y + 14.0,
+        end - start
+    )
+    .unwrap();
+    out.push_str("</svg>\n");
+    out
+}
+
+fn track_name(track: Track) -> String {
+    match track {
+        Track::Worker(id) => format!("worker {}", id),
+        Track::Fs(id) => format!("fs {}", id),
+    }
+}
+
+fn process_color(pid: u32) -> String {
+    // the golden angle spreads neighbouring ids around the wheel
+    format!("hsl({}, 65%, 55%)", pid as u64 * 137 % 360)
+}
+
+fn reason_color(reason: SpanEnd) -> &'static str {
+    match reason {
+        SpanEnd::Yield => "#4caf50",
+        SpanEnd::Preempted => "#ff9800",
+        SpanEnd::Blocked => "#9c27b0",
+        SpanEnd::Sleep => "#607d8b",
+        SpanEnd::Exit => "#f44336",
+        SpanEnd::FileRead | SpanEnd::FileWrite => "#2196f3",
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_and_spans() {
+        let ms = Duration::from_millis;
+        let share = Share::new();
+        let timeline = &share.timeline;
+        timeline.record(Track::Worker(0), 1, ms(0), ms(4), SpanEnd::Blocked);
+        timeline.record(Track::Fs(0), 1, ms(4), ms(6), SpanEnd::FileRead);
+        timeline.record(Track::Worker(0), 2, ms(4), ms(10), SpanEnd::Exit);
+
+        let svg = svg(&share);
+        let labels: Vec<_> = svg
+            .lines()
+            .filter_map(|l| l.strip_prefix("<text x=\"4\""))
+            .filter_map(|l| l.split('>').nth(1)?.strip_suffix("</text"))
+            .collect();
+        let rects: Vec<_> = svg.split("<rect").skip(1).collect();
+
+        assert_eq!(
+            labels,
+            ["Workers", "worker 0", "fs 0", "Processes", "#1", "#2"]
+        );
+        // once in the row of its track, once in the row of its process
+        assert_eq!(rects.len(), 6);
+        // 10ms over 1200px
+        assert!(rects[0].contains("x=\"140.00\" y=\"26.00\" width=\"480.00\""));
+        assert!(rects[0]
+            .contains("<title>#1 on worker 0\n0.0ns .. 4.0ms (4.0ms)\nend: blocked</title>"));
+        assert!(
+            rects[2].contains("<title>#1 on fs 0\n4.0ms .. 6.0ms (2.0ms)\nend: file read</title>")
+        );
+        assert!(rects[5].contains("fill=\"#f44336\""));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::workload;
+use crate::workload::ProcessSpec;
+use crate::workload::Step;
+use crate::workload::Workload;
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+/// Parse a trace of the Standard Workload Format of the Parallel Workloads
+/// Archive: a job per line, 18 fields, `-1` for unknown, `;` comments.
+///
+/// A job becomes a process per requested CPU, arriving at its submit time
+/// and running for its run time.
+pub fn swf(text: &str) -> io::Result<Workload> {
+    let mut processes = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let fields = line
+            .split_whitespace()
+            .map(|f| f.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid(format!("line {}: {}", n + 1, e)))?;
+
+        if fields.len() < 18 {
+            return Err(invalid(format!(
+                "line {}: {} fields, expected 18",
+                n + 1,
+                fields.len()
+            )));
+        }
+
+        let (job, submit, run) = (fields[0], fields[1], fields[3]);
+        // the requested CPUs, or the allocated ones when unknown
+        let cpus = match fields[7] {
+            c if c > 0.0 => c,
+            _ => fields[4],
+        };
+        let (user, group) = (fields[11], fields[12]);
+
+        // failed or cancelled before running
+        if run <= 0.0 || submit < 0.0 {
+            continue;
+        }
+
+        let secs = |s| workload::secs(s).map_err(|e| invalid(format!("line {}: {}", n + 1, e)));
+        processes.push(job_spec(
+            format!("job{} u{} g{}", job, user, group),
+            secs(submit)?,
+            secs(run)?,
+            cpus.max(1.0) as u32,
+            0,
+        ));
+    }
+
+    Ok(rebase(processes))
+}
+
+/// Parse a task events table in the style of the Google cluster traces: CSV
+/// lines of timestamp (us), missing info, job id, task index, machine id,
+/// event type, user, scheduling class, priority, CPU request, ... The first
+/// line may be a header.
+///
+/// A task becomes a process arriving at its SUBMIT event, with the priority
+/// of the trace, running from its SCHEDULE to its FINISH event.
+pub fn google(text: &str) -> io::Result<Workload> {
+    const SUBMIT: u32 = 0;
+    const SCHEDULE: u32 = 1;
+    const FINISH: u32 = 4;
+
+    #[derive(Default)]
+    struct Task {
+        user: String,
+        priority: i32,
+        submit: Option<u64>,
+        schedule: Option<u64>,
+        finish: Option<u64>,
+    }
+
+    let mut tasks: BTreeMap<(String, String), Task> = BTreeMap::new();
+    let mut first = true;
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line.split(',').collect();
+        let header = std::mem::take(&mut first) && fields[0].parse::<u64>().is_err();
+        if header {
+            continue;
+        }
+        if fields.len() < 9 {
+            return Err(invalid(format!(
+                "line {}: {} fields, expected at least 9",
+                n + 1,
+                fields.len()
+            )));
+        }
+
+        let parse = |i: usize| {
+            fields[i]
+                .parse::<u64>()
+                .map_err(|e| invalid(format!("line {}, field {}: {}", n + 1, i + 1, e)))
+        };
+        let time = parse(0)?;
+        let event = parse(5)? as u32;
+        let task = tasks
+            .entry((fields[2].to_string(), fields[3].to_string()))
+            .or_default();
+
+        match event {
+            SUBMIT => {
+                task.submit.get_or_insert(time);
+                task.user = fields[6].to_string();
+                task.priority = fields[8].parse().unwrap_or_default();
+            }
+            SCHEDULE => task.schedule = Some(time),
+            FINISH => task.finish = Some(time),
+            _ => {}
+        }
+    }
+
+    let processes = tasks
+        .into_iter()
+        .filter_map(|((job, index), task)| {
+            let run = task.finish?.checked_sub(task.schedule?)?;
+            let submit = task.submit.or(task.schedule)?;
+
+            Some(job_spec(
+                format!("{}.{} {}", job, index, task.user),
+                Duration::from_micros(submit),
+                Duration::from_micros(run),
+                1,
+                task.priority,
+            ))
+        })
+        .collect();
+
+    Ok(rebase(processes))
+}
+
+fn job_spec(
+    name: String,
+    arrival: Duration,
+    run: Duration,
+    cpus: u32,
+    priority: i32,
+) -> ProcessSpec {
+    ProcessSpec {
+        name,
+        priority,
+        arrival,
+        repeat: cpus,
+        loops: 1,
+        period: None,
+        wcet: None,
+        deadline: None,
+        steps: vec![Step::Burst(run)],
+    }
+}
+
+/// Make the first arrival time zero.
+fn rebase(mut processes: Vec<ProcessSpec>) -> Workload {
+    let first = processes
+        .iter()
+        .map(|p| p.arrival)
+        .min()
+        .unwrap_or_default();
+
+    for p in &mut processes {
+        p.arrival -= first;
+    }
+
+    Workload {
+        random: 0,
+        processes,
+        mutexes: Vec::new(),
+        semaphores: Vec::new(),
+    }
+}
+
+fn invalid(e: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bursts(w: &Workload) -> Vec<(&str, Duration, u32, Duration)> {
+        w.processes
+            .iter()
+            .map(|p| match p.steps[..] {
+                [Step::Burst(run)] => (&p.name[..], p.arrival, p.repeat, run),
+                _ => panic!("{:?}", p.steps),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn swf_jobs() {
+        let w = swf("; Version: 2.2
+             ; a comment
+
+             1 100 5 30 2 -1 -1 4 -1 -1 1 7 3 -1 -1 -1 -1 -1
+             2 110 0 -1 1 -1 -1 1 -1 -1 5 7 3 -1 -1 -1 -1 -1
+             3 125 2 0.5 1 -1 -1 -1 -1 -1 1 8 3 -1 -1 -1 -1 -1
+            ")
+        .unwrap();
+        let secs = Duration::from_secs_f64;
+
+        // job 2 was cancelled; job 3 asked for no CPU count, so gets the 1 it had
+        assert_eq!(
+            bursts(&w),
+            [
+                ("job1 u7 g3", secs(0.0), 4, secs(30.0)),
+                ("job3 u8 g3", secs(25.0), 1, secs(0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn swf_errors() {
+        let err = |text| swf(text).unwrap_err().to_string();
+
+        assert!(err("; x\n1 100 5 30").starts_with("line 2:"));
+        assert!(err("1 100 5 x 2 -1 -1 4 -1 -1 1 7 3 -1 -1 -1 -1 -1").starts_with("line 1:"));
+        assert!(err("1 100 5 1e300 2 -1 -1 4 -1 -1 1 7 3 -1 -1 -1 -1 -1").starts_with("line 1:"));
+        assert!(err("1 inf 5 30 2 -1 -1 4 -1 -1 1 7 3 -1 -1 -1 -1 -1").starts_with("line 1:"));
+    }
+
+    #[test]
+    fn google_tasks() {
+        let w = google(
+            "time,missing,job,task,machine,event,user,class,priority,cpu
+
+             1000000,,7,0,,0,alice,1,9,0.5
+             1000000,,7,1,,0,alice,1,9,0.5
+             1500000,,7,0,m1,1,alice,1,9,0.5
+             3500000,,7,0,m1,4,alice,1,9,0.5
+             2000000,,8,0,,0,bob,0,2,0.1
+             2000000,,8,0,m2,1,bob,0,2,0.1
+             2250000,,8,0,m2,4,bob,0,2,0.1
+            ",
+        )
+        .unwrap();
+
+        // task 7.1 never ran
+        assert_eq!(
+            bursts(&w),
+            [
+                ("7.0 alice", Duration::ZERO, 1, Duration::from_secs(2)),
+                (
+                    "8.0 bob",
+                    Duration::from_secs(1),
+                    1,
+                    Duration::from_millis(250)
+                ),
+            ]
+        );
+        assert_eq!(w.processes[0].priority, 9);
+    }
+
+    #[test]
+    fn google_errors() {
+        let err = |text| google(text).unwrap_err().to_string();
+
+        // only a first line may be a header
+        assert!(
+            err("1000000,,7,0,,0,alice,1,9\ntime,,job,task,,event,user,class,prio")
+                .starts_with("line 2,")
+        );
+        assert!(err("\n1000000,,7,0,,0,alice").starts_with("line 2:"));
+        assert!(err("1000000,,7,0,,x,alice,1,9").starts_with("line 1, field 6:"));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::ops::OpsRes;
+use crate::pcb::Pcb;
+use crate::scheduler::Share;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// What `Op::Send` leaves in the mailbox of a process.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub from: u32,
+    pub payload: String,
+    /// When it was sent, to measure the latency until the receiver runs.
+    pub sent: Duration,
+}
+
+/// The mailbox of every process, and the processes blocked on an empty one.
+#[derive(Default)]
+pub struct Mailboxes {
+    boxes: HashMap<u32, VecDeque<Message>>,
+    /// Processes in `Op::Recv`.
+    waiting: HashMap<u32, Pcb>,
+}
+
+impl Mailboxes {
+    /// Deliver `msg` to `to`, and return `to` if it was waiting for it. Fails
+    /// if there is no such process, or if it is done.
+    pub fn send(&mut self, to: u32, msg: Message, share: &Share) -> Result<Option<Pcb>, ()> {
+        match share.pinfo(to) {
+            Some(pinfo) if !pinfo.done => {}
+            _ => return Err(()),
+        }
+
+        if let Some(mut pcb) = self.waiting.remove(&to) {
+            pcb.ops_res = OpsRes::Received(msg);
+            return Ok(Some(pcb));
+        }
+        self.boxes.entry(to).or_default().push_back(msg);
+        Ok(None)
+    }
+
+    /// The oldest message for `id`, if any.
+    pub fn take(&mut self, id: u32) -> Option<Message> {
+        self.boxes.get_mut(&id)?.pop_front()
+    }
+
+    /// Block `pcb` until a message comes, unless one came already: then
+    /// `pcb` is back with it.
+    pub fn recv(&mut self, mut pcb: Pcb) -> Option<Pcb> {
+        match self.take(pcb.pinfo.id) {
+            Some(msg) => {
+                pcb.ops_res = OpsRes::Received(msg);
+                Some(pcb)
+            }
+            None => {
+                self.waiting.insert(pcb.pinfo.id, pcb);
+                None
+            }
+        }
+    }
+
+    pub fn blocked(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Nobody is left to send a message: take every process waiting for
+    /// one, with `OpsRes::NoMessage`.
+    pub fn hang_up(&mut self) -> Vec<Pcb> {
+        let mut woken: Vec<_> = self.waiting.drain().map(|(_, pcb)| pcb).collect();
+
+        woken.sort_by_key(|pcb| pcb.pinfo.id);
+        for pcb in &mut woken {
+            pcb.ops_res = OpsRes::NoMessage;
+        }
+        woken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::tests::simulate;
+    use std::time::Duration;
+
+    #[test]
+    fn messages_in_order() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "parent"
+            steps = [
+                { spawn = { name = "child", steps = [
+                    "recv", "recv",
+                    { send = { to = "sender", payload = "ack" } },
+                ] } },
+                { send = { to = "child", payload = "1" } },
+                { send = { to = "child", payload = "2" } },
+                "recv",
+                "wait",
+            ]
+            "#,
+        );
+
+        assert!(report.blocked.is_empty());
+        assert_eq!(report.processes.len(), 2);
+        assert!(report.processes.iter().all(|p| p.finish.is_some()));
+        assert_eq!(report.processes[0].received, 1);
+        assert_eq!(report.processes[1].received, 2);
+    }
+
+    #[test]
+    fn lone_recv_gives_up() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "lonely"
+            steps = ["recv", { burst = "1ms" }]
+            "#,
+        );
+
+        let p = &report.processes[0];
+        assert!(report.blocked.is_empty());
+        assert_eq!(p.finish, Some(Duration::from_millis(1)));
+        assert_eq!(p.received, 0);
+    }
+}
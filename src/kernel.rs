@@ -0,0 +1,310 @@
+use crate::clock;
+use crate::event::PriorityOp;
+use crate::event::SimEvent;
+use crate::family::Reap;
+use crate::fs::FileOp;
+use crate::ipc::Message;
+use crate::locks::BlockOn;
+use crate::ops::Op;
+use crate::ops::OpsRes;
+use crate::pcb::Pcb;
+use crate::pcb::ProcState;
+use crate::pcb::DEFAULT_TICKETS;
+use crate::pcb::MIN_TICKETS;
+use crate::pcb::TICKETS_PER_PRIORITY;
+use crate::scheduler::Share;
+use crate::timeline::MarkKind;
+use std::time::Duration;
+
+/// Where a process goes after its `Op` is applied.
+pub enum Dispatch {
+    /// Ready to run again.
+    Ready(Pcb),
+    /// Ready to run again, after the others.
+    Yield(Pcb),
+    /// Sleeps until that time.
+    Sleep(Pcb, Duration),
+    /// Ready to run again, and so is the other one: the child it spawned,
+    /// the process it sent a message to, or one it released a lock to.
+    ReadyWith(Pcb, Pcb),
+    /// Waits for that child, or any for `None`, to finish: hand it to `wait`.
+    Wait(Pcb, Option<u32>),
+    /// Waits for a message: hand it to `recv`.
+    Recv(Pcb),
+    /// Waits for a mutex, a semaphore or a condvar: hand it to `block`.
+    Block(Pcb, BlockOn),
+    /// Waits for the file op to be done.
+    Io(Pcb, FileOp),
+}
+
+/// Apply the `Op` a process returned from `poll` on `worker`, the same for
+/// the worker threads and the simulation. Called when the poll is over, so it also
+/// moves the process out of `ProcState::Running`.
+pub fn dispatch(mut pcb: Pcb, op: Op, worker: usize, share: &Share) -> Dispatch {
+    let msg_tx = pcb.msg_tx(Some(worker));
+    let priority_changed = |op, by| SimEvent::PriorityChanged {
+        op,
+        by,
+        priority: pcb.pinfo.metric.priority,
+    };
+
+    match op {
+        Op::None => {}
+        Op::FileOp(file_op) => {
+            pcb.set_state(ProcState::Blocked);
+            return Dispatch::Io(pcb, file_op);
+        }
+        Op::Yield => {
+            pcb.set_state(ProcState::Ready);
+            return Dispatch::Yield(pcb);
+        }
+        Op::Sleep(d) => {
+            pcb.set_state(ProcState::Blocked);
+            return Dispatch::Sleep(pcb, clock::now() + d);
+        }
+        Op::SleepUntil(t) => {
+            pcb.set_state(ProcState::Blocked);
+            return Dispatch::Sleep(pcb, t.max(clock::now()));
+        }
+        Op::Exit(_) => unreachable!("`Pcb::poll_wrap` turns it into `PollRes::Ready`"),
+        Op::Spawn(p) => {
+            let child = share.spawn(&pcb, Box::leak(p));
+
+            share.timeline.mark(
+                pcb.pinfo.id,
+                MarkKind::Spawn,
+                format!("spawn {}", child.pinfo.id),
+            );
+            pcb.ops_res = OpsRes::Spawned(child.pinfo.id);
+            pcb.set_state(ProcState::Ready);
+            return Dispatch::ReadyWith(pcb, child);
+        }
+        Op::Wait(_) | Op::WaitAny => {
+            let child = match op {
+                Op::Wait(child) => Some(child),
+                _ => None,
+            };
+
+            match share.family.lock().reap(pcb.pinfo.id, child, share) {
+                Reap::Pending => {
+                    pcb.set_state(ProcState::Blocked);
+                    return Dispatch::Wait(pcb, child);
+                }
+                reap => pcb = pcb.with_reap(reap),
+            }
+        }
+        Op::Send { to, payload } => {
+            let msg = Message {
+                from: pcb.pinfo.id,
+                payload,
+                sent: clock::now(),
+            };
+
+            match share.mailboxes.lock().send(to, msg, share) {
+                Ok(Some(receiver)) => {
+                    pcb.set_state(ProcState::Ready);
+                    return Dispatch::ReadyWith(pcb, receiver);
+                }
+                Ok(None) => {}
+                Err(()) => pcb.ops_res = OpsRes::NoProcess(to),
+            }
+        }
+        Op::Recv => match share.mailboxes.lock().take(pcb.pinfo.id) {
+            Some(msg) => pcb.ops_res = OpsRes::Received(msg),
+            None => {
+                pcb.set_state(ProcState::Blocked);
+                return Dispatch::Recv(pcb);
+            }
+        },
+        Op::Lock(id) => {
+            if !share.locks.lock().try_lock(id, &mut pcb, &share.timeline) {
+                pcb.set_state(ProcState::Blocked);
+                return Dispatch::Block(pcb, BlockOn::Lock(id));
+            }
+        }
+        Op::SemWait(id) => {
+            if !share.locks.lock().try_sem_wait(id) {
+                pcb.set_state(ProcState::Blocked);
+                return Dispatch::Block(pcb, BlockOn::Sem(id));
+            }
+        }
+        Op::CondWait { cond, lock } => {
+            if share.locks.lock().holds(lock, &pcb) {
+                pcb.set_state(ProcState::Blocked);
+                return Dispatch::Block(pcb, BlockOn::Cond { cond, lock });
+            }
+            pcb.ops_res = OpsRes::NotOwner(lock);
+        }
+        Op::Unlock(_) | Op::SemPost(_) | Op::CondSignal(_) | Op::CondBroadcast(_) => {
+            let mut locks = share.locks.lock();
+            let woken = match op {
+                Op::Unlock(id) => locks.unlock(id, &mut pcb, &share.timeline),
+                Op::SemPost(id) => locks.sem_post(id),
+                Op::CondSignal(cond) => locks.notify(cond, false, &share.timeline),
+                Op::CondBroadcast(cond) => locks.notify(cond, true, &share.timeline),
+                _ => unreachable!(),
+            };
+
+            if let Some(woken) = woken {
+                pcb.set_state(ProcState::Ready);
+                return Dispatch::ReadyWith(pcb, woken);
+            }
+        }
+        Op::AddPriority(p) => {
+            share
+                .locks
+                .lock()
+                .change_priority(pcb.pinfo, &share.timeline, |own| *own += p);
+            pcb.pinfo
+                .add_tickets(p.saturating_mul(TICKETS_PER_PRIORITY));
+
+            msg_tx.send(pcb.p.name(), priority_changed(PriorityOp::Add, p));
+        }
+        Op::SubPriority(p) => {
+            share
+                .locks
+                .lock()
+                .change_priority(pcb.pinfo, &share.timeline, |own| *own -= p);
+            pcb.pinfo
+                .add_tickets(p.saturating_mul(-TICKETS_PER_PRIORITY));
+            msg_tx.send(pcb.p.name(), priority_changed(PriorityOp::Sub, p));
+        }
+        Op::SetPriority(p) => {
+            share
+                .locks
+                .lock()
+                .change_priority(pcb.pinfo, &share.timeline, |own| *own = p);
+            let tickets =
+                (DEFAULT_TICKETS as i32).saturating_add(p.saturating_mul(TICKETS_PER_PRIORITY));
+            pcb.pinfo
+                .set_tickets(tickets.max(MIN_TICKETS as i32) as u32);
+            msg_tx.send(pcb.p.name(), priority_changed(PriorityOp::Set, p));
+        }
+        Op::TransferTickets { to, tickets } => {
+            if let Some(target) = share.pinfo(to) {
+                let own = pcb.pinfo.tickets().saturating_sub(MIN_TICKETS);
+                let got = target.add_tickets(tickets.min(own) as i32);
+
+                pcb.pinfo.add_tickets(-got);
+                msg_tx.send(
+                    pcb.p.name(),
+                    SimEvent::TicketsTransferred {
+                        to,
+                        tickets: got as u32,
+                    },
+                );
+            }
+        }
+    }
+
+    pcb.set_state(ProcState::Ready);
+    Dispatch::Ready(pcb)
+}
+
+/// Block `pcb` in the `Dispatch::Wait` it came back with, once it is off the
+/// worker. Returns it if the child finished meanwhile, to wake it up.
+pub fn wait(pcb: Pcb, child: Option<u32>, share: &Share) -> Option<Pcb> {
+    share.family.lock().wait(pcb, child, share)
+}
+
+/// Block `pcb` in the `Dispatch::Recv` it came back with, once it is off the
+/// worker. Returns it if a message came meanwhile, to wake it up.
+pub fn recv(pcb: Pcb, share: &Share) -> Option<Pcb> {
+    share.mailboxes.lock().recv(pcb)
+}
+
+/// Block `pcb` in the `Dispatch::Block` it came back with, once it is off the
+/// worker. Returns the process to wake up, if any.
+pub fn block(pcb: Pcb, on: BlockOn, share: &Share) -> Option<Pcb> {
+    share.locks.lock().block(pcb, on, &share.timeline)
+}
+
+/// `pcb` is done: a zombie until its parent reaps it. Returns the parent if
+/// that wakes it up.
+pub fn exit(pcb: Pcb, worker: usize, share: &Share) -> Option<Pcb> {
+    pcb.done(worker);
+    share.family.lock().exit(&pcb, share)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::Report;
+    use crate::sim::tests::simulate;
+    use std::time::Duration;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn finish(report: &Report, name: &str) -> Duration {
+        let p = report.processes.iter().find(|p| p.name == name).unwrap();
+        p.finish.unwrap()
+    }
+
+    #[test]
+    fn sleep_frees_the_cpu() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "sleeper"
+            steps = [{ burst = "2ms" }, { sleep = "10ms" }, { burst = "2ms" }, { exit = 0 }]
+
+            [[process]]
+            name = "other"
+            arrival = "3ms"
+            steps = [{ burst = "5ms" }, { exit = 0 }]
+            "#,
+        );
+
+        assert_eq!(finish(&report, "other"), ms(8));
+        assert_eq!(finish(&report, "sleeper"), ms(14));
+    }
+
+    #[test]
+    fn yield_lets_the_others_run() {
+        let run = |step: &str| {
+            let report = simulate(
+                "sjf",
+                1,
+                &format!(
+                    r#"
+                    [[process]]
+                    name = "other"
+                    steps = [{{ burst = "1ms" }}, {{ sleep = "1ms" }}, {{ burst = "1ms" }}, {{ exit = 0 }}]
+
+                    [[process]]
+                    name = "yielder"
+                    steps = [{{ burst = "2ms" }}, {}, {{ burst = "2ms" }}, {{ exit = 0 }}]
+                    "#,
+                    step
+                ),
+            );
+            (finish(&report, "yielder"), finish(&report, "other"))
+        };
+
+        // back from its sleep at 2ms, "other" is predicted the shorter burst,
+        // but SJF keeps "yielder" on the CPU until it blocks or yields
+        assert_eq!(run("{ burst = 0 }"), (ms(5), ms(6)));
+        assert_eq!(run("\"yield\""), (ms(6), ms(4)));
+    }
+
+    #[test]
+    fn exit_ends_the_steps() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "quitter"
+            steps = [{ burst = "1ms" }, { exit = 3 }, { burst = "5ms" }]
+            "#,
+        );
+        let p = &report.processes[0];
+
+        assert_eq!(p.finish, Some(ms(1)));
+        assert_eq!(p.cpu, ms(1));
+        assert_eq!(p.exit_code, 3);
+    }
+}
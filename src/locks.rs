@@ -0,0 +1,674 @@
+use crate::clock;
+use crate::ops::OpsRes;
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use crate::timeline::MarkKind;
+use crate::timeline::Timeline;
+use crate::util::convert::make_mut;
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The simulated mutexes, semaphores and condvars, and the processes
+/// blocked on them. They all come into being on first use.
+#[derive(Default)]
+pub struct Locks {
+    protocol: LockProtocol,
+    mutexes: HashMap<u32, Lock>,
+    semaphores: HashMap<u32, Semaphore>,
+    /// Processes in `Op::CondWait`, with the lock they take back.
+    conds: HashMap<u32, VecDeque<(Pcb, u32)>>,
+}
+
+/// How a mutex lends priority to the process holding it, against priority
+/// inversion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LockProtocol {
+    #[default]
+    None,
+    /// The holder runs with the highest priority of the processes blocked
+    /// on it, and of those blocked on them.
+    Inherit,
+    /// The holder runs with the ceiling the mutex is given, if any.
+    Ceiling,
+}
+
+/// What a process blocks on.
+pub enum BlockOn {
+    Lock(u32),
+    Sem(u32),
+    Cond { cond: u32, lock: u32 },
+}
+
+/// How a mutex or a semaphore was used over a run.
+#[derive(Debug, Clone, Default)]
+pub struct LockStats {
+    /// Times it was taken.
+    pub acquired: u32,
+    /// Times a process had to block for it.
+    pub contended: u32,
+    /// Time processes spent blocked on it.
+    pub waited: Duration,
+    /// Time it was held, for a mutex.
+    pub held: Duration,
+    pub max_held: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockKind {
+    Mutex,
+    Semaphore,
+}
+
+pub struct LockReport {
+    pub kind: LockKind,
+    pub id: u32,
+    pub stats: LockStats,
+}
+
+#[derive(Default)]
+struct Lock {
+    /// Who holds it, since when.
+    owner: Option<(&'static PInfo, Duration)>,
+    /// For `LockProtocol::Ceiling`, as given by `Locks::set_ceiling`.
+    ceiling: Option<i32>,
+    /// Blocked processes, since when, in the order they came.
+    waiting: VecDeque<(Pcb, Duration)>,
+    stats: LockStats,
+}
+
+#[derive(Default)]
+struct Semaphore {
+    count: u32,
+    waiting: VecDeque<(Pcb, Duration)>,
+    stats: LockStats,
+}
+
+impl Lock {
+    fn take(&mut self, pinfo: &'static PInfo, now: Duration) {
+        self.owner = Some((pinfo, now));
+        self.stats.acquired += 1;
+    }
+
+    fn owner(&self) -> Option<u32> {
+        self.owner.map(|(pinfo, _)| pinfo.id)
+    }
+
+    /// Hand it over to the waiting process with the highest priority, the
+    /// first come of those, if any, and return it.
+    fn release(&mut self, now: Duration) -> Option<Pcb> {
+        if let Some((_, since)) = self.owner.take() {
+            self.stats.held += now - since;
+            self.stats.max_held = self.stats.max_held.max(now - since);
+        }
+
+        let (next, _) = self
+            .waiting
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, (pcb, _))| (pcb.pinfo.metric.priority, Reverse(i)))?;
+        let (pcb, since) = self.waiting.remove(next).unwrap();
+        self.stats.waited += now - since;
+        self.take(pcb.pinfo, now);
+        Some(pcb)
+    }
+}
+
+impl Locks {
+    pub fn set_protocol(&mut self, protocol: LockProtocol) {
+        self.protocol = protocol;
+    }
+
+    /// The ceiling of mutex `id` for `LockProtocol::Ceiling`, which lends
+    /// nothing without one.
+    pub fn set_ceiling(&mut self, id: u32, ceiling: i32) {
+        self.mutexes.entry(id).or_default().ceiling = Some(ceiling);
+    }
+
+    /// A semaphore starting at `count`, instead of 0.
+    pub fn init_sem(&mut self, id: u32, count: u32) {
+        self.semaphores.entry(id).or_default().count = count;
+    }
+
+    /// Take lock `id` for `pcb`, unless another process holds it. Returns
+    /// whether `pcb` goes on, rather than blocks.
+    pub fn try_lock(&mut self, id: u32, pcb: &mut Pcb, timeline: &Timeline) -> bool {
+        let lock = self.mutexes.entry(id).or_default();
+
+        match lock.owner() {
+            None => lock.take(pcb.pinfo, clock::now()),
+            Some(owner) if owner == pcb.pinfo.id => pcb.ops_res = OpsRes::Deadlock(id),
+            Some(_) => return false,
+        }
+        self.settle(pcb.pinfo, timeline);
+        true
+    }
+
+    pub fn holds(&self, id: u32, pcb: &Pcb) -> bool {
+        let owner = self.mutexes.get(&id).and_then(|l| l.owner());
+        owner == Some(pcb.pinfo.id)
+    }
+
+    /// Release lock `id` held by `pcb`, and return the process it goes to.
+    pub fn unlock(&mut self, id: u32, pcb: &mut Pcb, timeline: &Timeline) -> Option<Pcb> {
+        if !self.holds(id, pcb) {
+            pcb.ops_res = OpsRes::NotOwner(id);
+            return None;
+        }
+        let next = self.mutexes.get_mut(&id).unwrap().release(clock::now());
+
+        self.settle(pcb.pinfo, timeline);
+        if let Some(next) = &next {
+            self.settle(next.pinfo, timeline);
+        }
+        next
+    }
+
+    /// Take one of semaphore `id`, if any is left. Returns whether it did.
+    pub fn try_sem_wait(&mut self, id: u32) -> bool {
+        let sem = self.semaphores.entry(id).or_default();
+
+        if sem.count == 0 {
+            return false;
+        }
+        sem.count -= 1;
+        sem.stats.acquired += 1;
+        true
+    }
+
+    /// Give one to semaphore `id`, and return the process it goes to.
+    pub fn sem_post(&mut self, id: u32) -> Option<Pcb> {
+        let sem = self.semaphores.entry(id).or_default();
+
+        let Some((pcb, since)) = sem.waiting.pop_front() else {
+            sem.count += 1;
+            return None;
+        };
+        sem.stats.waited += clock::now() - since;
+        sem.stats.acquired += 1;
+        Some(pcb)
+    }
+
+    /// Wake the first process waiting on `cond`, or all of them. They take
+    /// their lock back first: returns the one which got it right away.
+    pub fn notify(&mut self, cond: u32, all: bool, timeline: &Timeline) -> Option<Pcb> {
+        let waiting = self.conds.entry(cond).or_default();
+        let n = match all {
+            true => waiting.len(),
+            false => waiting.len().min(1),
+        };
+        let mut ready = None;
+
+        for (pcb, lock) in waiting.drain(..n).collect::<Vec<_>>() {
+            // once one got it, the others queue up
+            if let Some(pcb) = self.block(pcb, BlockOn::Lock(lock), timeline) {
+                ready = Some(pcb);
+            }
+        }
+        ready
+    }
+
+    /// Block `pcb` on what it waits for. Returns a process to wake up: `pcb`
+    /// if that came free meanwhile, or for a condvar the one the lock went to.
+    pub fn block(&mut self, mut pcb: Pcb, on: BlockOn, timeline: &Timeline) -> Option<Pcb> {
+        let now = clock::now();
+
+        match on {
+            BlockOn::Lock(id) => {
+                if self.try_lock(id, &mut pcb, timeline) {
+                    return Some(pcb);
+                }
+                let lock = self.mutexes.get_mut(&id).unwrap();
+                lock.stats.contended += 1;
+                lock.waiting.push_back((pcb, now));
+                self.lend(id, timeline);
+                None
+            }
+            BlockOn::Sem(id) => {
+                if self.try_sem_wait(id) {
+                    return Some(pcb);
+                }
+                let sem = self.semaphores.get_mut(&id).unwrap();
+                sem.stats.contended += 1;
+                sem.waiting.push_back((pcb, now));
+                None
+            }
+            BlockOn::Cond { cond, lock } => {
+                let next = self.mutexes.get_mut(&lock).unwrap().release(now);
+
+                self.settle(pcb.pinfo, timeline);
+                if let Some(next) = &next {
+                    self.settle(next.pinfo, timeline);
+                }
+                self.conds.entry(cond).or_default().push_back((pcb, lock));
+                next
+            }
+        }
+    }
+
+    /// The priority mutex `id` lends to its holder, if any.
+    fn lent(&self, id: u32) -> Option<i32> {
+        let lock = &self.mutexes[&id];
+
+        match self.protocol {
+            LockProtocol::None => None,
+            LockProtocol::Inherit => lock
+                .waiting
+                .iter()
+                .map(|(pcb, _)| pcb.pinfo.metric.priority)
+                .max(),
+            LockProtocol::Ceiling => lock.ceiling,
+        }
+    }
+
+    /// Change the own priority of `pinfo`, whether or not it runs with a
+    /// lent one, as every priority is changed with the locks locked.
+    pub fn change_priority(
+        &self,
+        pinfo: &'static PInfo,
+        timeline: &Timeline,
+        f: impl FnOnce(&mut i32),
+    ) {
+        // Safety: as in `settle`
+        f(unsafe { make_mut(pinfo) }.own_priority_mut());
+        // the mutexes it holds may lend it more, or less, than its own now
+        self.settle(pinfo, timeline);
+    }
+
+    /// Set the priority of `pinfo` to its own, or to the highest one lent by
+    /// the mutexes it holds, and mark any change on `timeline`.
+    fn settle(&self, pinfo: &'static PInfo, timeline: &Timeline) {
+        let lent = self
+            .mutexes
+            .iter()
+            .filter(|(_, l)| l.owner() == Some(pinfo.id))
+            .filter_map(|(&id, _)| self.lent(id))
+            .max();
+        // Safety: once a process is spawned, its priority is only written
+        // with the locks locked, so no two writes race. The reads do not lock:
+        // the worker or the run queue of the holder, and the printer, see the
+        // old priority or the new one. The policies ordering by it keep it from
+        // when the holder was queued (`Queued`, the weights of `Cfs`), so a
+        // run queue never sees it change under it: `Queued::pop` queues the
+        // holder again with the new one.
+        let pinfo = unsafe { make_mut(pinfo) };
+        let (old, was_lent) = (pinfo.metric.priority, pinfo.base_priority.is_some());
+        let own = pinfo.base_priority.unwrap_or(old);
+
+        match lent {
+            Some(lent) if lent > own => {
+                pinfo.metric.priority = lent;
+                pinfo.base_priority = Some(own);
+            }
+            _ => {
+                pinfo.metric.priority = own;
+                pinfo.base_priority = None;
+            }
+        }
+
+        if pinfo.metric.priority != old {
+            let why = match (was_lent, pinfo.base_priority.is_some()) {
+                (_, true) => " lent",
+                (true, false) => " restored",
+                (false, false) => "",
+            };
+            timeline.mark(
+                pinfo.id,
+                MarkKind::Priority,
+                format!("priority {} => {}{}", old, pinfo.metric.priority, why),
+            );
+        }
+    }
+
+    /// A process blocked on mutex `id`: lend its priority to the holder,
+    /// and on to the holder of the mutex that one is blocked on, if any.
+    fn lend(&self, mut id: u32, timeline: &Timeline) {
+        // bounded, in case of a deadlock
+        for _ in 0..self.mutexes.len() {
+            let Some((owner, _)) = self.mutexes[&id].owner else {
+                return;
+            };
+            self.settle(owner, timeline);
+
+            let blocked_on = self
+                .mutexes
+                .iter()
+                .find(|(_, l)| l.waiting.iter().any(|(pcb, _)| pcb.pinfo.id == owner.id));
+            match blocked_on {
+                Some((&next, _)) => id = next,
+                None => return,
+            }
+        }
+    }
+
+    pub fn blocked(&self) -> usize {
+        let mutexes: usize = self.mutexes.values().map(|l| l.waiting.len()).sum();
+        let semaphores: usize = self.semaphores.values().map(|s| s.waiting.len()).sum();
+        let conds: usize = self.conds.values().map(VecDeque::len).sum();
+
+        mutexes + semaphores + conds
+    }
+
+    /// Take every blocked process, with what it blocks on.
+    pub fn drain(&mut self) -> Vec<(Pcb, String)> {
+        let mut blocked = Vec::new();
+
+        for (id, lock) in &mut self.mutexes {
+            blocked.extend(
+                lock.waiting
+                    .drain(..)
+                    .map(|(pcb, _)| (pcb, format!("mutex {}", id))),
+            );
+        }
+        for (id, sem) in &mut self.semaphores {
+            blocked.extend(
+                sem.waiting
+                    .drain(..)
+                    .map(|(pcb, _)| (pcb, format!("semaphore {}", id))),
+            );
+        }
+        for (id, waiting) in &mut self.conds {
+            blocked.extend(
+                waiting
+                    .drain(..)
+                    .map(|(pcb, _)| (pcb, format!("condvar {}", id))),
+            );
+        }
+        blocked
+    }
+
+    /// Every mutex and semaphore used, mutexes first.
+    pub fn reports(&self) -> Vec<LockReport> {
+        let report = |kind, id: &u32, stats: &LockStats| LockReport {
+            kind,
+            id: *id,
+            stats: stats.clone(),
+        };
+        let mut reports: Vec<_> = self
+            .mutexes
+            .iter()
+            .map(|(id, l)| report(LockKind::Mutex, id, &l.stats))
+            .chain(
+                self.semaphores
+                    .iter()
+                    .map(|(id, s)| report(LockKind::Semaphore, id, &s.stats)),
+            )
+            .collect();
+
+        reports.sort_by_key(|r| (r.kind, r.id));
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy;
+    use crate::report::Report;
+    use crate::scheduler::SchedulerBuilder;
+    use crate::sim::tests::simulate;
+    use crate::sim::tests::simulate_share;
+    use crate::sim::tests::simulate_with;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn finishes(report: &Report) -> Vec<Option<Duration>> {
+        report.processes.iter().map(|p| p.finish).collect()
+    }
+
+    fn stats(report: &Report, kind: LockKind, id: u32) -> &LockStats {
+        let lock = report.locks.iter().find(|l| l.kind == kind && l.id == id);
+        &lock.unwrap().stats
+    }
+
+    #[test]
+    fn mutex_lets_one_in() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "p"
+            repeat = 2
+            steps = [{ lock = 1 }, { burst = "5ms" }, { unlock = 1 }, { exit = 0 }]
+            "#,
+        );
+        let stats = stats(&report, LockKind::Mutex, 1);
+
+        assert_eq!(finishes(&report), [Some(ms(5)), Some(ms(10))]);
+        assert_eq!((stats.acquired, stats.contended), (2, 1));
+        assert_eq!(
+            (stats.waited, stats.held, stats.max_held),
+            (ms(5), ms(10), ms(5))
+        );
+    }
+
+    #[test]
+    fn mutex_goes_to_the_highest_priority() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "holder"
+            steps = [{ lock = 1 }, { burst = "5ms" }, { unlock = 1 }, { exit = 0 }]
+
+            [[process]]
+            name = "low"
+            priority = 1
+            arrival = "1ms"
+            steps = [{ lock = 1 }, { burst = "2ms" }, { unlock = 1 }, { exit = 0 }]
+
+            [[process]]
+            name = "high"
+            priority = 5
+            arrival = "2ms"
+            steps = [{ lock = 1 }, { burst = "2ms" }, { unlock = 1 }, { exit = 0 }]
+            "#,
+        );
+
+        // high came last but takes it first, at 5ms; holder exits last
+        assert_eq!(finishes(&report), [Some(ms(9)), Some(ms(9)), Some(ms(7))]);
+    }
+
+    #[test]
+    fn semaphore_lets_count_in() {
+        let report = simulate(
+            "priority",
+            3,
+            r#"
+            [[semaphore]]
+            id = 1
+            count = 2
+
+            [[process]]
+            name = "p"
+            repeat = 3
+            steps = [{ sem_wait = 1 }, { burst = "4ms" }, { sem_post = 1 }, { exit = 0 }]
+            "#,
+        );
+        let stats = stats(&report, LockKind::Semaphore, 1);
+
+        assert_eq!(finishes(&report), [Some(ms(4)), Some(ms(4)), Some(ms(8))]);
+        assert_eq!((stats.acquired, stats.contended), (3, 1));
+        assert_eq!(stats.waited, ms(4));
+    }
+
+    #[test]
+    fn condvar_takes_the_lock_back() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "consumer"
+            steps = [
+                { lock = 2 },
+                { cond_wait = { cond = 1, lock = 2 } },
+                { burst = "1ms" },
+                { unlock = 2 },
+                { exit = 0 },
+            ]
+
+            [[process]]
+            name = "producer"
+            arrival = "10ms"
+            steps = [
+                { burst = "2ms" },
+                { lock = 2 },
+                { cond_signal = 1 },
+                { burst = "3ms" },
+                { unlock = 2 },
+                { exit = 0 },
+            ]
+            "#,
+        );
+
+        // signalled at 12ms, the consumer waits for the producer to unlock
+        assert!(report.blocked.is_empty());
+        assert_eq!(finishes(&report), [Some(ms(16)), Some(ms(15))]);
+    }
+
+    #[test]
+    fn deadlocks_end_the_run() {
+        let report = simulate(
+            "priority",
+            3,
+            r#"
+            [[process]]
+            name = "a"
+            steps = [{ lock = 1 }, { burst = "2ms" }, { lock = 2 }, { unlock = 2 }, { unlock = 1 }]
+
+            [[process]]
+            name = "b"
+            steps = [{ lock = 2 }, { burst = "2ms" }, { lock = 1 }, { unlock = 1 }, { unlock = 2 }]
+
+            [[process]]
+            name = "relocker"
+            steps = [{ lock = 3 }, { lock = 3 }, { burst = "1ms" }, { unlock = 3 }, { exit = 0 }]
+            "#,
+        );
+        let mut blocked = report.blocked.clone();
+        blocked.sort();
+
+        assert_eq!(
+            blocked,
+            [(1, "mutex 2".to_string()), (2, "mutex 1".to_string())]
+        );
+        // locking a mutex twice is reported to the process, not a deadlock
+        assert_eq!(finishes(&report), [None, None, Some(ms(1))]);
+    }
+
+    /// The turnaround of each process of the Pathfinder workload, on one CPU.
+    fn pathfinder(protocol: LockProtocol) -> Vec<(String, Duration)> {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name("priority").unwrap());
+        let report = simulate_with(
+            builder,
+            1,
+            protocol,
+            include_str!("../workloads/pathfinder.toml"),
+        );
+
+        report
+            .processes
+            .iter()
+            .map(|p| (p.name.clone(), p.turnaround.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn protocols_bound_priority_inversion() {
+        let bus = |protocol| {
+            let turnarounds = pathfinder(protocol);
+            turnarounds
+                .into_iter()
+                .find(|(name, _)| name == "bus")
+                .unwrap()
+                .1
+        };
+
+        // comms keeps meteo, and so bus, off the CPU for its 20 loops
+        assert_eq!(bus(LockProtocol::None), ms(115));
+        // meteo runs with the priority of bus until it unlocks
+        assert_eq!(bus(LockProtocol::Inherit), ms(20));
+        assert_eq!(bus(LockProtocol::Ceiling), ms(20));
+    }
+
+    #[test]
+    fn lent_priorities_are_marked() {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name("priority").unwrap());
+        let (report, share) = simulate_share(
+            builder,
+            1,
+            LockProtocol::Inherit,
+            include_str!("../workloads/pathfinder.toml"),
+        );
+        let meteo = report.processes.iter().find(|p| p.name == "meteo").unwrap();
+        let marks: Vec<_> = share
+            .timeline
+            .marks()
+            .iter()
+            .filter(|m| m.pid == meteo.id && m.kind == MarkKind::Priority)
+            .map(|m| (m.at, m.name.clone()))
+            .collect();
+
+        // bus blocks once the first burst of meteo is over
+        assert_eq!(
+            marks,
+            [
+                (ms(5), "priority 0 => 10 lent".to_string()),
+                (ms(15), "priority 10 => 0 restored".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ceiling_is_the_given_one() {
+        // how long middle waits for holder to unlock
+        let middle = |mutex: &str| {
+            let workload = format!(
+                r#"
+                {}
+
+                [[process]]
+                name = "early"
+                priority = 10
+                steps = [{{ lock = 1 }}, {{ unlock = 1 }}, {{ exit = 0 }}]
+
+                [[process]]
+                name = "holder"
+                arrival = "1ms"
+                steps = [
+                    {{ lock = 1 }},
+                    {{ burst = "2ms" }},
+                    {{ burst = "3ms" }},
+                    {{ unlock = 1 }},
+                    {{ exit = 0 }},
+                ]
+
+                [[process]]
+                name = "middle"
+                priority = 5
+                arrival = "2ms"
+                steps = [{{ burst = "10ms" }}, {{ exit = 0 }}]
+                "#,
+                mutex
+            );
+            let builder =
+                SchedulerBuilder::new().boxed_policy(policy::by_name("priority").unwrap());
+            let report = simulate_with(builder, 1, LockProtocol::Ceiling, &workload);
+
+            report.processes[2].response.unwrap()
+        };
+
+        assert_eq!(middle("[[mutex]]\nid = 1\nceiling = 10"), ms(4));
+        // early took it with priority 10, which raises no ceiling: middle
+        // only waits for the burst of holder to end
+        assert_eq!(middle("[[mutex]]\nid = 1\nceiling = 3"), ms(1));
+        assert_eq!(middle(""), ms(1));
+    }
+}
@@ -1,48 +1,67 @@
-#![feature(div_duration)]
-
+use crate::event::Msg;
+use crate::event::MsgFormat;
 use crate::pcb::PInfo;
-use crate::pcb::Pcb;
 use crate::printer::Printer;
+use crate::report::Comparison;
+use crate::report::Report;
 use crate::scheduler::Share;
+use crate::util::convert::make_mut;
+use crate::util::convert::StaticRef;
+use crate::util::convert::StaticRefArray;
+use clap::Parser;
+use cli::Cli;
+use cli::Command;
+use cli::RunArgs;
 use crossbeam::sync::Parker;
 use crossbeam_channel::unbounded;
 use crossbeam_channel::Receiver;
-use crossbeam_epoch::default_collector;
-use crossbeam_epoch::pin;
-use crossbeam_skiplist_piedb::SkipList;
+use crossbeam_channel::Sender;
 use msg_receiver::MsgReceiver;
-use parking_lot::Condvar;
-use parking_lot::Mutex;
-use pcb::Process;
 use processes::*;
+use rng::Rng;
+use scheduler::Executor;
 use scheduler::SchedulerBuilder;
+use sink::SinkSpec;
 use std::collections::LinkedList;
-use std::env;
-use std::hash::BuildHasherDefault;
 use std::io;
 use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use work_queue::Queue;
-use wutil::convert::make_mut;
-use wutil::convert::StaticRef;
-use wutil::convert::StaticRefArray;
-use wutil::static_refs;
-use wutil::types::SStr;
+use workload::ScriptProcess;
+use workload::Workload;
 
+mod cli;
+mod clock;
+mod event;
+mod family;
 mod fs;
+mod gantt;
+mod import;
+mod ipc;
+mod kernel;
+mod locks;
 mod msg_receiver;
 mod ops;
 mod pcb;
+mod policy;
 mod printer;
 mod processes;
+mod report;
+mod rng;
 mod scheduler;
+mod sim;
+mod sink;
+mod timeline;
+mod timer;
+mod trace;
+mod util;
 mod worker_info;
+mod workload;
 
 macro_rules! make_pcbs {
 	($($p:ident, $pinfo:ident, $t:ident;)+) => {
-		wutil::static_refs_mut! {
+		crate::static_refs_mut! {
 			$(
 				$p = $t::new();
 				$pinfo = PInfo::new();
@@ -54,8 +73,46 @@ macro_rules! make_pcbs {
 const RANDOM_PROCESSES: usize = 20;
 
 fn main() -> io::Result<()> {
-    let threads = num_cpus::get();
-    // let threads = 3;
+    match Cli::parse().command {
+        Command::Run(args) => {
+            run(&args)?;
+        }
+        Command::Replay { file, mut run } => {
+            run.workload = Some(file);
+            run.virtual_time = true;
+            self::run(&run)?;
+        }
+        Command::Compare { policies, mut run } => {
+            // the same processes, the same seed: only the policy changes
+            run.virtual_time = true;
+            run.quiet = true;
+            run.seed.get_or_insert_with(Rng::random_seed);
+
+            let reports = policies
+                .into_iter()
+                .map(|policy| {
+                    self::run(&RunArgs {
+                        policy,
+                        ..run.clone()
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+
+            print!("{}", Comparison(&reports));
+        }
+        Command::Report { files } => {
+            for file in files {
+                println!("{}", Report::read_csv(&file)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &RunArgs) -> io::Result<Report> {
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    // the printer takes its start point from the clock
+    clock::set_virtual(args.virtual_time);
     static_refs! {
         global_queue = Queue::new(threads as usize, 32);
         share = Share::new();
@@ -65,32 +122,69 @@ fn main() -> io::Result<()> {
         fs_unparkers = Vec::with_capacity(threads);
     };
 
-    let msg_addr = env::args().nth(1).unwrap();
+    let seed = args.seed.unwrap_or_else(Rng::random_seed);
     let (msg_tx, msg_rx) = unbounded();
-    start_assitor(msg_rx, msg_addr, msg_done)?;
-
-    // make pcbs
-    make_pcbs! {
-        p1,p1info,P1;
-        p2,p2info,P2;
-        p3,p3info,P3;
-        p4,p4info,P4;
-        p5,p5info,P5;
-    };
+    start_assitor(msg_rx, &args.msg, args.msg_format, msg_done)?;
 
-    // make parkers
-    let parkers = StaticRefArray::new(threads as usize, || {
-        let parker = Parker::new();
-        let unparker = parker.unparker().clone();
-        unsafe { make_mut(fs_unparkers) }.push(unparker.clone());
+    let mut workload = args.workload.as_deref().map(Workload::load).transpose()?;
+    if let (Some(w), Some(scale)) = (&mut workload, args.time_scale) {
+        w.scale(scale)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    let mut locks = share.locks.lock();
+    locks.set_protocol(args.lock_protocol);
+    for mutex in workload.iter().flat_map(|w| &w.mutexes) {
+        locks.set_ceiling(mutex.id, mutex.ceiling);
+    }
+    for sem in workload.iter().flat_map(|w| &w.semaphores) {
+        locks.init_sem(sem.id, sem.count);
+    }
+    drop(locks);
 
-        (parker, unparker)
-    });
+    // We use factory to create any amount random processes
+    let random = args
+        .random
+        .or(workload.as_ref().map(|w| w.random))
+        .unwrap_or(RANDOM_PROCESSES);
+    let mut factory = RandomFactory::new(random, seed);
+    // like the factory, the workload processes must outlive the scheduler
+    let mut scripts = workload.map(|w| w.instantiate());
 
-    // Safety: The life of it go along with the scheduler
-    let parkers = unsafe { (&parkers).static_ref() };
+    let mut builder = SchedulerBuilder::new()
+        .boxed_policy(policy::by_name(&args.policy).unwrap())
+        .time_slice(args.time_slice)
+        .print(!args.quiet)
+        .print_interval(args.print_interval)
+        .seed(seed)
+        .preempt(args.preempt);
+    if let Some(path) = &args.report {
+        builder = builder.report_csv(path);
+    }
+    if let Some(path) = &args.gantt {
+        builder = builder.gantt(path);
+    }
+    if let Some(path) = &args.trace {
+        builder = builder.trace(path);
+    }
 
-    let mut s = SchedulerBuilder::new().build(
+    if args.virtual_time {
+        let mut sim = builder.simulate(threads, printer, share, worker_infos, msg_done);
+
+        submit(&mut sim, msg_tx, &mut factory, scripts.as_mut(), seed);
+        return Ok(sim.run());
+    }
+
+    // make parkers, one per worker, which the others unpark
+    let parkers = (0..threads)
+        .map(|_| {
+            let parker = Parker::new();
+            unsafe { make_mut(fs_unparkers) }.push(parker.unparker().clone());
+
+            parker
+        })
+        .collect();
+
+    let mut s = builder.build(
         threads,
         global_queue,
         printer,
@@ -99,38 +193,91 @@ fn main() -> io::Result<()> {
         parkers,
         msg_done,
         fs_unparkers,
-        msg_tx.clone(),
     );
-    s.execute(p1, msg_tx.clone(), p1info);
-    s.execute(p2, msg_tx.clone(), p2info);
-    s.execute(p3, msg_tx.clone(), p3info);
-    s.execute(p4, msg_tx.clone(), p4info);
-    s.execute(p5, msg_tx.clone(), p5info);
+    submit(&mut s, msg_tx, &mut factory, scripts.as_mut(), seed);
+
+    // MUST call it, or be memory of out bounds!
+    Ok(s.join())
+}
+
+/// Run the built-in processes, or the ones of the workload, and the random ones.
+fn submit(
+    s: &mut impl Executor,
+    msg_tx: Sender<Msg>,
+    factory: &mut RandomFactory,
+    scripts: Option<&mut Vec<ScriptProcess>>,
+    seed: u64,
+) {
+    if scripts.is_none() {
+        // make pcbs
+        make_pcbs! {
+            p1,p1info,P1;
+            p2,p2info,P2;
+            p3,p3info,P3;
+            p4,p4info,P4;
+            p5,p5info,P5;
+        };
+        crate::static_refs_mut! {
+            p6 = PeriodicProcess::new(
+                "P6",
+                Duration::from_millis(100),
+                Duration::from_millis(20),
+                50,
+                Rng::stream(seed, rng::PERIODIC),
+            );
+            p6info = PInfo::new();
+            p7 = PeriodicProcess::new(
+                "P7",
+                Duration::from_millis(250),
+                Duration::from_millis(60),
+                20,
+                Rng::stream(seed, rng::PERIODIC + 1),
+            );
+            p7info = PInfo::new();
+        };
+
+        s.execute(p1, msg_tx.clone(), p1info);
+        s.execute(p2, msg_tx.clone(), p2info);
+        s.execute(p3, msg_tx.clone(), p3info);
+        s.execute(p4, msg_tx.clone(), p4info);
+        s.execute(p5, msg_tx.clone(), p5info);
+        s.execute(p6, msg_tx.clone(), p6info);
+        s.execute(p7, msg_tx.clone(), p7info);
+    }
 
-    // We use factory to create any amount random processes
-    let mut factory = RandomFactory::new(RANDOM_PROCESSES);
     let pinfos = StaticRefArray::new(factory.len(), Default::default);
 
     for (r, pinfo) in &mut factory.zip(pinfos.iter()) {
         s.execute(r, msg_tx.clone(), pinfo);
     }
 
-    drop(msg_tx);
-    // MUST call one of below, or be memory of out bounds!
-    // s.infinite_run();
-    s.join();
-    Ok(())
+    if let Some(scripts) = scripts {
+        let pinfos = StaticRefArray::new(scripts.len(), Default::default);
+
+        for (p, pinfo) in scripts.iter_mut().zip(pinfos.iter()) {
+            let (arrival, priority) = (p.arrival(), p.priority());
+
+            s.execute_at(
+                arrival.into(),
+                unsafe { p.static_ref_mut() },
+                msg_tx.clone(),
+                pinfo,
+                priority,
+            );
+        }
+    }
 }
 
 fn start_assitor(
-    msg_rx: Receiver<SStr>,
-    addr: String,
+    msg_rx: Receiver<Msg>,
+    sink: &SinkSpec,
+    format: MsgFormat,
     msg_done: &'static AtomicBool,
 ) -> io::Result<()> {
-    let mut r = MsgReceiver::new(msg_rx, msg_done)?;
+    let mut r = MsgReceiver::new(msg_rx, sink.open()?, format, msg_done);
 
     thread::spawn(move || {
-        r.start(&addr);
+        r.start();
     });
     Ok(())
 }
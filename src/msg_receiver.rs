@@ -1,30 +1,54 @@
+use crate::event::Msg;
+use crate::event::MsgFormat;
+use crate::sink::MsgSink;
 use crossbeam_channel::Receiver;
-use std::io;
-use std::io::Write;
-use std::net::TcpStream;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use wutil::types::SStr;
 
 pub struct MsgReceiver {
-    rx: Receiver<SStr>,
+    rx: Receiver<Msg>,
+    sink: Box<dyn MsgSink>,
+    format: MsgFormat,
     msg_done: &'static AtomicBool,
 }
 
 impl MsgReceiver {
-    pub fn new(rx: Receiver<SStr>, msg_done: &'static AtomicBool) -> io::Result<Self> {
-        Ok(Self { rx, msg_done })
+    pub fn new(
+        rx: Receiver<Msg>,
+        sink: Box<dyn MsgSink>,
+        format: MsgFormat,
+        msg_done: &'static AtomicBool,
+    ) -> Self {
+        Self {
+            rx,
+            sink,
+            format,
+            msg_done,
+        }
     }
 
-    pub fn start(&mut self, addr: &str) -> io::Result<()> {
-        for s in &self.rx {
-            let mut stream = TcpStream::connect(addr)?;
-            stream.write(s.as_bytes())?;
+    /// Render every message in `format` and send it to the sink until all
+    /// the senders are gone. A message the sink fails on is dropped, the
+    /// others still go out, and `msg_done` is set in any case.
+    pub fn start(&mut self) {
+        let mut dropped = 0;
+
+        for msg in &self.rx {
+            if let Err(e) = self.sink.send(&msg.render(self.format)) {
+                if dropped == 0 {
+                    eprintln!("message sink: {}, dropping messages", e);
+                }
+                dropped += 1;
+            }
         }
 
-        println!("{:?}", 222);
+        if let Err(e) = self.sink.flush() {
+            eprintln!("message sink: {}", e);
+        }
+        dropped += self.sink.dropped();
+        if dropped > 0 {
+            eprintln!("message sink: {} messages dropped", dropped);
+        }
         self.msg_done.store(true, Ordering::Relaxed);
-
-        Ok(())
     }
 }
@@ -1,6 +1,6 @@
 use crate::fs::FileOp;
-use std::fs::File;
-use std::io;
+use crate::ipc::Message;
+use crate::pcb::Process;
 use std::io::Error;
 use std::time::Duration;
 
@@ -10,24 +10,84 @@ pub enum Op {
     SubPriority(i32),
     SetPriority(i32),
     FileOp(FileOp),
+    /// Give `tickets` of our own lottery/stride tickets to the process `to`.
+    TransferTickets {
+        to: u32,
+        tickets: u32,
+    },
+    /// Give the worker up, even when the policy would not preempt us.
+    Yield,
+    /// Block for that long, without holding a worker.
+    Sleep(Duration),
+    /// Block until that `clock::now`, as `Sleep` does.
+    SleepUntil(Duration),
+    /// Terminate with this exit code, returning `PollRes::Ready` exits with 0.
+    Exit(i32),
+    /// Start a child process, its id comes back as `OpsRes::Spawned`.
+    Spawn(Box<dyn Process>),
+    /// Block until that child finished, and reap it.
+    Wait(u32),
+    /// Block until any child finished, and reap it.
+    WaitAny,
+    /// Leave a message in the mailbox of the process `to`, without blocking.
+    Send {
+        to: u32,
+        payload: String,
+    },
+    /// Take the oldest message of our mailbox, blocking while it is empty.
+    Recv,
+    /// Take that simulated mutex, blocking while another process holds it.
+    Lock(u32),
+    /// Release that mutex, to the process blocked on it first.
+    Unlock(u32),
+    /// Take one of that semaphore, blocking while there is none left.
+    SemWait(u32),
+    SemPost(u32),
+    /// Release `lock` and block until the condvar `cond` is signaled, then
+    /// take `lock` back.
+    CondWait {
+        cond: u32,
+        lock: u32,
+    },
+    /// Wake the process waiting on that condvar first.
+    CondSignal(u32),
+    /// Wake every process waiting on that condvar.
+    CondBroadcast(u32),
 }
 
-#[derive(Debug)]
+/// What the op of the last poll came to, for the next poll. The processes
+/// read the fields they need.
+#[derive(Debug, Default)]
 pub enum OpsRes {
+    #[default]
     Empty,
     FileReadRes(FileReadRes),
     FileWriteRes(FileWriteRes),
-}
-
-impl Default for OpsRes {
-    fn default() -> Self {
-        OpsRes::Empty
-    }
+    /// The id of the child of `Op::Spawn`.
+    Spawned(u32),
+    /// The child reaped by `Op::Wait` or `Op::WaitAny`, and its exit code.
+    Exited {
+        child: u32,
+        code: i32,
+    },
+    /// There was no such child to wait for.
+    NoChild,
+    /// What `Op::Recv` got.
+    Received(Message),
+    /// `Op::Recv` gave up: every other process was done or blocked, so no
+    /// message could come.
+    NoMessage,
+    /// The process of `Op::Send` does not exist, or is done.
+    NoProcess(u32),
+    /// `Op::Lock` of a mutex we hold already.
+    Deadlock(u32),
+    /// `Op::Unlock` or `Op::CondWait` of a mutex we do not hold.
+    NotOwner(u32),
 }
 
 #[derive(Debug)]
 pub enum FileReadRes {
-    Ok(usize),
+    Ok,
     FileBufReturnNone,
     Err(Error),
 }
@@ -44,8 +104,9 @@ pub enum Stone {
     Ops(OpsType),
 }
 
+/// What a process blocked on between two bursts. There are no network ops,
+/// so no `Net` kind.
 #[derive(Debug)]
 pub enum OpsType {
     File,
-    Net,
 }
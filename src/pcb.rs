@@ -1,21 +1,27 @@
+use crate::clock;
+use crate::event::Msg;
+use crate::event::MsgTx;
+use crate::event::SimEvent;
 use crate::ops::Op;
 use crate::ops::OpsRes;
 use crate::ops::Stone;
-use crate::scheduler::Share;
+use crate::util::convert;
+use crate::util::convert::make_mut;
+use crate::util::convert::StaticRef;
 use core::fmt;
-use crossbeam_channel::Receiver;
 use crossbeam_channel::Sender;
-use parking_lot::Mutex;
 use std::collections::LinkedList;
 use std::mem::take;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
-use wutil::convert;
-use wutil::convert::make_mut;
-use wutil::convert::StaticRef;
-use wutil::types::SStr;
-use wutil::util::time_test;
 
 pub const INIT_PRIORITY: i32 = 0;
+pub const DEFAULT_TICKETS: u32 = 100;
+pub const MIN_TICKETS: u32 = 1;
+/// A process can never hold more tickets than this, whatever it is given.
+pub const MAX_TICKETS: u32 = 10_000;
+pub const TICKETS_PER_PRIORITY: i32 = 10;
 
 pub enum PollRes {
     Polling(Op),
@@ -24,10 +30,22 @@ pub enum PollRes {
 
 pub trait Process: fmt::Debug + Send + Sync {
     fn name(&self) -> &String;
-    fn poll(&mut self, msg_tx: Sender<SStr>, ops_res: OpsRes) -> PollRes;
+    fn poll(&mut self, msg_tx: MsgTx, ops_res: OpsRes) -> PollRes;
     fn file_buf(&mut self) -> Option<&mut String> {
         None
     }
+    /// A periodic real-time process releases one job (one `poll`) per period.
+    fn period(&self) -> Option<Duration> {
+        None
+    }
+    /// The worst case execution time of one job.
+    fn wcet(&self) -> Option<Duration> {
+        None
+    }
+    /// The relative deadline of each job, the period by default.
+    fn deadline(&self) -> Option<Duration> {
+        self.period()
+    }
 }
 
 #[derive(Debug)]
@@ -35,19 +53,148 @@ pub struct Pcb {
     pub p: &'static mut dyn Process,
     pub pinfo: &'static PInfo,
     pub ops_res: OpsRes,
-    pub msg_tx: Sender<SStr>,
+    pub msg_tx: Sender<Msg>,
+    pub pending: Option<Pending>,
 }
 
-#[derive(Debug, Default)]
+/// The rest of a burst cut by preemption, and what the poll returned.
+pub struct Pending {
+    pub rest: Duration,
+    pub poll_res: PollRes,
+}
+
+impl fmt::Debug for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pending").field("rest", &self.rest).finish()
+    }
+}
+
+#[derive(Debug)]
 pub struct PInfo {
     pub id: u32,
     pub metric: Metric,
+    /// Its own priority, while it runs with one lent by a mutex it holds.
+    pub base_priority: Option<i32>,
     pub name: Option<&'static String>,
     pub run_slices: f32,
+    /// The MLFQ level, `None` when the policy has no levels.
+    pub level: Option<usize>,
+    pub boost_epoch: u32,
+    pub vruntime: Duration,
+    /// Lottery and stride tickets, other processes may transfer theirs here.
+    pub tickets: AtomicU32,
+    pub pass: u64,
+    pub rt: Option<RtTask>,
+    pub burst: Burst,
+    pub preemptions: u32,
+    pub state: ProcState,
+    pub state_since: Duration,
+    /// The time spent in each state, indexed like `ProcState::ALL`.
+    pub state_times: [Duration; ProcState::ALL.len()],
+    /// When each state was entered first, indexed like `ProcState::ALL`.
+    pub first_entered: [Option<Duration>; ProcState::ALL.len()],
     pub done: bool,
+    /// 0 unless the process ended with `Op::Exit`.
+    pub exit_code: i32,
+    /// The process which spawned this one, if any.
+    pub parent: Option<u32>,
+    /// The messages received, and their total time from `Op::Send` to the
+    /// poll which got them.
+    pub received: u32,
+    pub msg_latency: Duration,
     pub stones: LinkedList<Stone>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcState {
+    /// Known to the scheduler, not admitted yet.
+    New,
+    /// Waiting in a run queue.
+    Ready,
+    Running,
+    /// Waiting for an operation, such as a file op.
+    Blocked,
+    /// Done, but not reaped yet.
+    Zombie,
+    Terminated,
+}
+
+impl ProcState {
+    pub const ALL: [ProcState; 6] = [
+        ProcState::New,
+        ProcState::Ready,
+        ProcState::Running,
+        ProcState::Blocked,
+        ProcState::Zombie,
+        ProcState::Terminated,
+    ];
+}
+
+impl fmt::Display for ProcState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ProcState::New => "NEW",
+            ProcState::Ready => "RDY",
+            ProcState::Running => "RUN",
+            ProcState::Blocked => "BLK",
+            ProcState::Zombie => "ZMB",
+            ProcState::Terminated => "END",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug)]
+pub struct RtTask {
+    pub period: Duration,
+    pub wcet: Duration,
+    pub deadline: Duration,
+    pub release: Duration,
+    pub jobs: u32,
+    pub misses: u32,
+    /// Whether it passed the admission test, otherwise it runs as best effort.
+    pub admitted: bool,
+}
+
+impl RtTask {
+    pub fn new(p: &dyn Process) -> Option<Self> {
+        let (period, wcet) = (p.period()?, p.wcet()?);
+
+        Some(Self {
+            period,
+            wcet,
+            deadline: p.deadline().unwrap_or(period),
+            release: clock::now(),
+            jobs: 0,
+            misses: 0,
+            admitted: true,
+        })
+    }
+
+    pub fn utilization(&self) -> f64 {
+        self.wcet.as_secs_f64() / self.period.as_secs_f64()
+    }
+
+    /// The absolute deadline of the current job.
+    pub fn abs_deadline(&self) -> Duration {
+        self.release + self.period * self.jobs + self.deadline
+    }
+}
+
+/// The CPU burst prediction of SJF/SRTF and how wrong it was.
+#[derive(Debug, Default)]
+pub struct Burst {
+    pub predicted: Option<Duration>,
+    pub error: Duration,
+    pub bursts: u32,
+}
+
+impl Burst {
+    pub fn avg_error(&self) -> Duration {
+        self.error.checked_div(self.bursts).unwrap_or_default()
+    }
+}
+
 const EACH_COMPENSATE: Duration = Duration::from_millis(20);
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -80,13 +227,13 @@ impl Eq for Pcb {}
 
 impl PartialOrd for Metric {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.value().partial_cmp(&self.value())
+        Some(self.cmp(other))
     }
 }
 
 impl PartialOrd for Pcb {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.pinfo.metric.cmp(&other.pinfo.metric))
+        Some(self.cmp(other))
     }
 }
 
@@ -98,7 +245,7 @@ impl Ord for Pcb {
 
 impl Ord for Metric {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        unsafe { self.partial_cmp(other).unwrap_unchecked() }
+        other.value().total_cmp(&self.value())
     }
 }
 
@@ -108,7 +255,24 @@ impl PInfo {
             id: 0,
             name: None,
             run_slices: 0.0,
+            level: None,
+            boost_epoch: 0,
+            vruntime: Duration::ZERO,
+            tickets: AtomicU32::new(DEFAULT_TICKETS),
+            pass: 0,
+            rt: None,
+            burst: Burst::default(),
+            preemptions: 0,
+            state: ProcState::New,
+            state_since: Duration::ZERO,
+            state_times: [Duration::ZERO; ProcState::ALL.len()],
+            first_entered: [None; ProcState::ALL.len()],
             done: false,
+            exit_code: 0,
+            parent: None,
+            received: 0,
+            msg_latency: Duration::ZERO,
+            base_priority: None,
             metric: Metric {
                 priority: INIT_PRIORITY,
                 running_time: Duration::ZERO,
@@ -118,11 +282,87 @@ impl PInfo {
     }
 }
 
+impl Default for PInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PInfo {
+    /// Its own priority, whether or not it runs with a lent one.
+    pub fn own_priority_mut(&mut self) -> &mut i32 {
+        match &mut self.base_priority {
+            Some(own) => own,
+            None => &mut self.metric.priority,
+        }
+    }
+
+    /// Enter `ProcState::New`, as the process is created.
+    pub fn create(&mut self) {
+        self.state_since = clock::now();
+        self.first_entered[ProcState::New as usize] = Some(self.state_since);
+    }
+
+    pub fn set_state(&mut self, state: ProcState) {
+        let now = clock::now();
+
+        self.state_times[self.state as usize] += now.saturating_sub(self.state_since);
+        self.state = state;
+        self.state_since = now;
+        self.first_entered[state as usize].get_or_insert(now);
+    }
+
+    /// When the process entered `state` first, if it did.
+    pub fn first(&self, state: ProcState) -> Option<Duration> {
+        self.first_entered[state as usize]
+    }
+
+    /// The time spent in `state` so far, the current one included.
+    pub fn time_in(&self, state: ProcState) -> Duration {
+        let mut time = self.state_times[state as usize];
+
+        if self.state == state {
+            time += clock::now().saturating_sub(self.state_since);
+        }
+        time
+    }
+
+    /// The CPU time since the last blocking operation.
+    pub fn current_burst(&self) -> Duration {
+        match self.stones.back() {
+            Some(Stone::Time(d)) => *d,
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn tickets(&self) -> u32 {
+        self.tickets.load(Ordering::Relaxed)
+    }
+
+    /// Add (or remove) `delta` tickets, kept in `MIN_TICKETS..=MAX_TICKETS`.
+    /// Returns how many it actually added.
+    pub fn add_tickets(&self, delta: i32) -> i32 {
+        let add =
+            |t: u32| (t as i64 + delta as i64).clamp(MIN_TICKETS as i64, MAX_TICKETS as i64) as u32;
+        let old = self
+            .tickets
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |t| Some(add(t)))
+            .unwrap();
+
+        add(old) as i32 - old as i32
+    }
+
+    pub fn set_tickets(&self, tickets: u32) {
+        self.tickets
+            .store(tickets.clamp(MIN_TICKETS, MAX_TICKETS), Ordering::Relaxed);
+    }
+}
+
 impl Pcb {
     pub fn new(
         id: u32,
         p: &'static dyn Process,
-        msg_tx: Sender<SStr>,
+        msg_tx: Sender<Msg>,
         pinfo: &'static PInfo,
     ) -> Self {
         // Safety: here in main thread single thread.
@@ -135,26 +375,85 @@ impl Pcb {
             msg_tx,
             pinfo,
             ops_res: OpsRes::Empty,
+            pending: None,
         }
     }
 
-    fn pinfo_mut(&self) -> &mut PInfo {
+    #[allow(clippy::mut_from_ref)]
+    pub fn pinfo_mut(&self) -> &mut PInfo {
         // Safety: the process is unique! So is safe
-        unsafe { make_mut(&self.pinfo) }
+        unsafe { make_mut(self.pinfo) }
     }
 
-    pub fn done(&self) {
-        // Safety: the process is unique! So is safe
-        self.pinfo_mut().done = true;
+    pub fn set_state(&self, state: ProcState) {
+        self.pinfo_mut().set_state(state);
     }
 
-    pub fn poll_wrap(&mut self, time_slice: Duration) -> PollRes {
-        let ops_res = take(&mut self.ops_res);
+    /// Messages from the process, while on `worker` if any.
+    pub fn msg_tx(&self, worker: Option<usize>) -> MsgTx {
+        MsgTx::new(self.msg_tx.clone(), self.pinfo.id, worker)
+    }
+
+    pub fn done(&self, worker: usize) {
+        self.msg_tx(Some(worker)).send(
+            self.p.name(),
+            SimEvent::Finished {
+                code: self.pinfo.exit_code,
+                cpu_us: self.pinfo.metric.running_time.as_micros() as u64,
+            },
+        );
         // Safety: the process is unique! So is safe
-        let (time, poll_res) =
-            time_test(|| unsafe { convert::make_mut(self).p.poll(self.msg_tx.clone(), ops_res) });
         let pinfo = self.pinfo_mut();
-        let slices = time.div_duration_f32(time_slice);
+
+        pinfo.done = true;
+        pinfo.set_state(ProcState::Zombie);
+    }
+
+    /// Poll the process on `worker`, or go on with the burst it was preempted in. When
+    /// `preempt`, a burst longer than `time_slice` is cut and the process
+    /// only sees its result once the whole burst ran.
+    pub fn poll_wrap(
+        &mut self,
+        worker: usize,
+        time_slice: Duration,
+        preempt: bool,
+    ) -> (Duration, PollRes) {
+        let budget = preempt.then_some(time_slice);
+        let start = clock::now();
+
+        self.set_state(ProcState::Running);
+        let (rest, (time, poll_res)) = clock::with_budget(budget, || match self.pending.take() {
+            Some(Pending { rest, poll_res }) => {
+                let (time, ()) = clock::measure(|| clock::burn(rest));
+                (time, poll_res)
+            }
+            None => {
+                let ops_res = take(&mut self.ops_res);
+                if let OpsRes::Received(msg) = &ops_res {
+                    let pinfo = self.pinfo_mut();
+                    pinfo.received += 1;
+                    pinfo.msg_latency += start.saturating_sub(msg.sent);
+                }
+                let msg_tx = self.msg_tx(Some(worker));
+                // Safety: the process is unique! So is safe
+                clock::measure(|| unsafe { convert::make_mut(self).p.poll(msg_tx, ops_res) })
+            }
+        });
+        let (poll_res, preempted) = if rest > Duration::ZERO {
+            self.pending = Some(Pending { rest, poll_res });
+            (PollRes::Polling(Op::None), true)
+        } else {
+            (poll_res, false)
+        };
+        let poll_res = match poll_res {
+            PollRes::Polling(Op::Exit(code)) => {
+                self.pinfo_mut().exit_code = code;
+                PollRes::Ready
+            }
+            poll_res => poll_res,
+        };
+        let pinfo = self.pinfo_mut();
+        let slices = (time.as_secs_f64() / time_slice.as_secs_f64()) as f32;
 
         pinfo.name = unsafe { Some(self.p.name().static_ref()) };
         pinfo.run_slices += slices;
@@ -177,6 +476,102 @@ impl Pcb {
                 .push_back(Stone::Time(time));
         }
 
-        poll_res
+        if preempted {
+            self.pinfo_mut().preemptions += 1;
+        } else if let (Some(rt), PollRes::Polling(_)) = (&mut self.pinfo_mut().rt, &poll_res) {
+            // the virtual clock stays at `start` until the simulation advances it
+            if start + time > rt.abs_deadline() {
+                rt.misses += 1;
+            }
+            rt.jobs += 1;
+        }
+
+        (time, poll_res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locks::LockProtocol;
+    use crate::policy;
+    use crate::report::Report;
+    use crate::scheduler::SchedulerBuilder;
+    use crate::sim::tests::simulate_with;
+
+    const TWO_LONG: &str = r#"
+        [[process]]
+        name = "a"
+        steps = [{ burst = "100ms" }, { exit = 0 }]
+
+        [[process]]
+        name = "b"
+        steps = [{ burst = "100ms" }, { exit = 0 }]
+    "#;
+
+    fn preempted(policy: &str, workload: &str) -> Report {
+        let builder = SchedulerBuilder::new()
+            .boxed_policy(policy::by_name(policy).unwrap())
+            .time_slice(Duration::from_millis(20))
+            .preempt(true);
+
+        simulate_with(builder, 1, LockProtocol::None, workload)
+    }
+
+    fn finish(report: &Report, name: &str) -> Duration {
+        let p = report.processes.iter().find(|p| p.name == name).unwrap();
+        p.finish.unwrap()
+    }
+
+    #[test]
+    fn tickets_stay_in_bounds() {
+        let pinfo = PInfo::new();
+
+        pinfo.set_tickets(5);
+        assert_eq!(pinfo.add_tickets(-10), -4);
+        assert_eq!(pinfo.tickets(), MIN_TICKETS);
+        assert_eq!(
+            pinfo.add_tickets(MAX_TICKETS as i32),
+            MAX_TICKETS as i32 - 1
+        );
+        assert_eq!(pinfo.add_tickets(1), 0);
+    }
+
+    #[test]
+    fn slices_by_the_time_slice() {
+        let report = preempted("priority", TWO_LONG);
+
+        // 20ms each in turn, by running time
+        assert_eq!(finish(&report, "a"), Duration::from_millis(180));
+        assert_eq!(finish(&report, "b"), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn slices_by_the_mlfq_level() {
+        let report = preempted("mlfq", TWO_LONG);
+
+        // 20ms on level 0, 40ms on level 1, then 80ms
+        assert_eq!(finish(&report, "a"), Duration::from_millis(160));
+        assert_eq!(finish(&report, "b"), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn slices_by_the_cfs_share() {
+        let report = preempted(
+            "cfs",
+            r#"
+            [[process]]
+            name = "a"
+            steps = [{ burst = "24ms" }, { exit = 0 }]
+
+            [[process]]
+            name = "b"
+            steps = [{ burst = "24ms" }, { exit = 0 }]
+            "#,
+        );
+
+        // half of sched_latency each
+        assert_eq!(finish(&report, "a"), Duration::from_millis(36));
+        assert_eq!(finish(&report, "b"), Duration::from_millis(48));
     }
 }
@@ -0,0 +1,266 @@
+use crate::pcb::Metric;
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+mod cfs;
+mod lottery;
+mod mlfq;
+mod realtime;
+mod sjf;
+mod stride;
+
+pub use cfs::Cfs;
+pub use lottery::Lottery;
+pub use mlfq::Mlfq;
+pub use realtime::Edf;
+pub use realtime::Rm;
+pub use sjf::Sjf;
+pub use sjf::Srtf;
+pub use stride::Stride;
+
+/// The names `by_name` knows.
+pub const NAMES: [&str; 9] = [
+    "priority", "mlfq", "cfs", "lottery", "stride", "sjf", "srtf", "edf", "rm",
+];
+
+/// The policy called `name`, with its default configuration.
+pub fn by_name(name: &str) -> Option<Box<dyn SchedulingPolicy>> {
+    Some(match name {
+        "priority" => Box::new(PriorityPolicy::new()),
+        "mlfq" => Box::new(Mlfq::default()),
+        "cfs" => Box::new(Cfs::default()),
+        "lottery" => Box::new(Lottery::new()),
+        "stride" => Box::new(Stride::new()),
+        "sjf" => Box::new(Sjf::default()),
+        "srtf" => Box::new(Srtf::default()),
+        "edf" => Box::new(Edf::new()),
+        "rm" => Box::new(Rm::new()),
+        _ => return None,
+    })
+}
+
+/// The run queue of one worker thread.
+///
+/// Every worker owns its own instance (made by `fork`), so any state that
+/// must follow a process across workers belongs in its `PInfo`.
+pub trait SchedulingPolicy: Send {
+    fn name(&self) -> &'static str;
+
+    /// A new empty policy with the same configuration, used for each worker.
+    fn fork(&self) -> Box<dyn SchedulingPolicy>;
+
+    /// Remove and return the process which should run next.
+    fn pick_next(&mut self) -> Option<Pcb>;
+
+    /// A process becomes ready on this worker.
+    fn on_enqueue(&mut self, pcb: Pcb);
+
+    /// A process returned from `poll` after running `used`.
+    fn on_tick(&mut self, _pcb: &Pcb, _used: Duration) {}
+
+    /// How long the process of `pinfo` may run in one poll, when preemptive:
+    /// `time_slice`, the base one, unless the policy slices its own way.
+    fn quantum(&self, _pinfo: &PInfo, time_slice: Duration) -> Duration {
+        time_slice
+    }
+
+    /// Whether the process gives up the worker after `on_tick`, otherwise
+    /// it is polled again right away.
+    fn preempt(&mut self, _pcb: &Pcb) -> bool {
+        true
+    }
+
+    /// A process leaves the worker for a blocking operation.
+    fn on_block(&mut self, _pcb: &Pcb) {}
+
+    /// A process is done.
+    fn on_exit(&mut self, _pcb: &Pcb) {}
+
+    /// Reseed the randomness of the policy, if any, with the seed of its worker.
+    fn seed(&mut self, _seed: u64) {}
+
+    fn len(&self) -> usize;
+
+    /// The admission test for real-time processes: can periodic processes
+    /// of these `utilizations` be scheduled on `workers`.
+    fn admit(&self, _utilizations: &[f64], _workers: usize) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A process in a heap, ordered by its `Metric` as it was when queued: a
+/// mutex may lend it a priority meanwhile, which must not change the order
+/// under the heap. `pop` queues such processes again first.
+pub struct Queued(Metric, Pcb);
+
+impl Queued {
+    pub fn new(pcb: Pcb) -> Self {
+        Self(pcb.pinfo.metric, pcb)
+    }
+
+    pub fn into_pcb(self) -> Pcb {
+        self.1
+    }
+
+    fn stale(&self) -> bool {
+        self.0.priority != self.1.pinfo.metric.priority
+    }
+
+    /// Pop the best process of `heap`, by the priorities they have now.
+    pub fn pop(heap: &mut BinaryHeap<Queued>) -> Option<Pcb> {
+        if heap.iter().any(Queued::stale) {
+            let pcbs = std::mem::take(heap).into_vec();
+            heap.extend(pcbs.into_iter().map(|q| Queued::new(q.into_pcb())));
+        }
+        heap.pop().map(Queued::into_pcb)
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// The original ordering: `Metric::value()` (running time minus priority
+/// compensation) in a binary heap.
+#[derive(Default)]
+pub struct PriorityPolicy {
+    heap: BinaryHeap<Queued>,
+}
+
+impl PriorityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SchedulingPolicy for PriorityPolicy {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self::new())
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        Queued::pop(&mut self.heap)
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        self.heap.push(Queued::new(pcb));
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::event::MsgTx;
+    use crate::ops::OpsRes;
+    use crate::pcb::PollRes;
+    use crate::pcb::Process;
+    use crate::util::convert::make_mut;
+    use crossbeam_channel::unbounded;
+
+    /// A process to queue, never polled.
+    #[derive(Debug)]
+    struct Idle(String);
+
+    impl Process for Idle {
+        fn name(&self) -> &String {
+            &self.0
+        }
+
+        fn poll(&mut self, _: MsgTx, _: OpsRes) -> PollRes {
+            PollRes::Ready
+        }
+    }
+
+    /// Process `id`, with `f` applied to its `PInfo` first.
+    pub fn pcb(id: u32, f: impl FnOnce(&mut PInfo)) -> Pcb {
+        let pinfo = Box::leak(Box::new(PInfo::new()));
+        let p = Box::leak(Box::new(Idle(format!("p{}", id))));
+
+        f(pinfo);
+        Pcb::new(id, p, unbounded().0, pinfo)
+    }
+
+    /// Run whatever `policy` picks for `slice` at a time, `n` times, and
+    /// return the ids it picked.
+    pub fn run(policy: &mut dyn SchedulingPolicy, slice: Duration, n: usize) -> Vec<u32> {
+        let mut picked = Vec::new();
+
+        for _ in 0..n {
+            let pcb = policy.pick_next().unwrap();
+            picked.push(pcb.pinfo.id);
+            pcb.pinfo_mut().metric.running_time += slice;
+            policy.on_tick(&pcb, slice);
+            policy.on_enqueue(pcb);
+        }
+        picked
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn priority_takes_turns_by_running_time() {
+        let mut policy = PriorityPolicy::new();
+        policy.on_enqueue(pcb(1, |_| {}));
+        policy.on_enqueue(pcb(2, |p| p.metric.running_time = ms(5)));
+
+        assert_eq!(run(&mut policy, ms(10), 4), [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn priority_compensates_running_time() {
+        let mut policy = PriorityPolicy::new();
+        policy.on_enqueue(pcb(1, |p| p.metric.priority = 1));
+        policy.on_enqueue(pcb(2, |_| {}));
+
+        // 20ms of running time per level of priority
+        assert_eq!(run(&mut policy, ms(15), 6), [1, 1, 2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn priority_changed_while_queued() {
+        let mut policy = PriorityPolicy::new();
+        policy.on_enqueue(pcb(1, |_| {}));
+        policy.on_enqueue(pcb(2, |p| p.metric.running_time = ms(5)));
+
+        // a mutex lends process 2 a priority
+        let p2 = policy.heap.iter().map(|q| q.1.pinfo).find(|p| p.id == 2);
+        unsafe { make_mut(p2.unwrap()) }.metric.priority = 1;
+
+        assert_eq!(policy.pick_next().unwrap().pinfo.id, 2);
+        assert_eq!(policy.pick_next().unwrap().pinfo.id, 1);
+        assert!(policy.pick_next().is_none());
+    }
+}
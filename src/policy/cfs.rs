@@ -0,0 +1,214 @@
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use crate::policy::SchedulingPolicy;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Same as the kernel's `sched_prio_to_weight`, nice -20 first.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+const NICE_0_WEIGHT: u64 = 1024;
+
+/// A higher `Metric::priority` means a lower nice: nice = -priority,
+/// clamped into -20..=19.
+pub fn nice(priority: i32) -> i32 {
+    (-priority).clamp(-20, 19)
+}
+
+pub fn weight(priority: i32) -> u64 {
+    NICE_TO_WEIGHT[(nice(priority) + 20) as usize]
+}
+
+/// Completely Fair Scheduler.
+///
+/// Runs the process with the smallest weighted virtual runtime, and lets it
+/// keep the worker until it used its share of `sched_latency`.
+pub struct Cfs {
+    /// By vruntime, with the weight each process was queued with: a mutex
+    /// may lend it a priority meanwhile.
+    run_queue: BTreeMap<(Duration, u32), (Pcb, u64)>,
+    queue_weight: u64,
+    sched_latency: Duration,
+    min_granularity: Duration,
+    // nanos, shared by every fork since processes move between workers
+    min_vruntime: Arc<AtomicU64>,
+    slice: Duration,
+    slice_used: Duration,
+}
+
+impl Cfs {
+    pub fn new(sched_latency: Duration, min_granularity: Duration) -> Self {
+        Self {
+            run_queue: BTreeMap::new(),
+            queue_weight: 0,
+            sched_latency,
+            min_granularity,
+            min_vruntime: Arc::new(AtomicU64::new(0)),
+            slice: Duration::ZERO,
+            slice_used: Duration::ZERO,
+        }
+    }
+
+    fn min_vruntime(&self) -> Duration {
+        Duration::from_nanos(self.min_vruntime.load(Ordering::Relaxed))
+    }
+
+    fn update_min_vruntime(&self, vruntime: Duration) {
+        let leftmost = match self.run_queue.keys().next() {
+            Some(&(v, _)) => v.min(vruntime),
+            None => vruntime,
+        };
+
+        self.min_vruntime
+            .fetch_max(leftmost.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The wall time `weight` gets out of one scheduling period.
+    fn ideal_slice(&self, weight: u64) -> Duration {
+        let nr_running = self.run_queue.len() as u32 + 1;
+        let period = self.sched_latency.max(self.min_granularity * nr_running);
+        let total = self.queue_weight + weight;
+
+        (period * weight as u32 / total as u32).max(self.min_granularity)
+    }
+}
+
+impl Default for Cfs {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(24), Duration::from_millis(3))
+    }
+}
+
+impl SchedulingPolicy for Cfs {
+    fn name(&self) -> &'static str {
+        "cfs"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self {
+            run_queue: BTreeMap::new(),
+            queue_weight: 0,
+            sched_latency: self.sched_latency,
+            min_granularity: self.min_granularity,
+            min_vruntime: self.min_vruntime.clone(),
+            slice: Duration::ZERO,
+            slice_used: Duration::ZERO,
+        })
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        let (_, (pcb, weight)) = self.run_queue.pop_first()?;
+
+        self.queue_weight -= weight;
+        self.slice = self.ideal_slice(weight);
+        self.slice_used = Duration::ZERO;
+
+        Some(pcb)
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        let pinfo = pcb.pinfo_mut();
+        // a new or waking process must not bring a huge credit, but is
+        // allowed to run a bit ahead of the others
+        let floor = self.min_vruntime().saturating_sub(self.sched_latency / 2);
+
+        pinfo.vruntime = pinfo.vruntime.max(floor);
+        let weight = weight(pinfo.metric.priority);
+
+        self.queue_weight += weight;
+        self.run_queue
+            .insert((pinfo.vruntime, pinfo.id), (pcb, weight));
+    }
+
+    fn on_tick(&mut self, pcb: &Pcb, used: Duration) {
+        let pinfo = pcb.pinfo_mut();
+        let delta = used.as_nanos() as u64 * NICE_0_WEIGHT / weight(pinfo.metric.priority);
+
+        pinfo.vruntime += Duration::from_nanos(delta);
+        self.slice_used += used;
+        self.update_min_vruntime(pinfo.vruntime);
+    }
+
+    /// What is left of its slice.
+    fn quantum(&self, _pinfo: &PInfo, _time_slice: Duration) -> Duration {
+        self.slice.saturating_sub(self.slice_used)
+    }
+
+    fn preempt(&mut self, pcb: &Pcb) -> bool {
+        if self.slice_used >= self.slice {
+            return true;
+        }
+
+        // someone further left is waiting
+        match self.run_queue.keys().next() {
+            Some(&(v, _)) => v < pcb.pinfo.vruntime,
+            None => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.run_queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+    use crate::policy::tests::run;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn runs_in_proportion_to_weight() {
+        let mut cfs = Cfs::default();
+        // nice -5 against nice 0
+        cfs.on_enqueue(pcb(1, |p| p.metric.priority = 5));
+        cfs.on_enqueue(pcb(2, |_| {}));
+
+        let picked = run(&mut cfs, ms(1), 4145);
+        let first = picked.iter().filter(|&&id| id == 1).count();
+
+        assert!((3100..3140).contains(&first), "{}", first);
+    }
+
+    #[test]
+    fn slices_the_latency_by_weight() {
+        let mut cfs = Cfs::default();
+        cfs.on_enqueue(pcb(1, |_| {}));
+        cfs.on_enqueue(pcb(2, |_| {}));
+
+        let p = cfs.pick_next().unwrap();
+        assert_eq!(cfs.quantum(p.pinfo, ms(100)), ms(12));
+
+        cfs.on_tick(&p, ms(5));
+        assert_eq!(cfs.quantum(p.pinfo, ms(100)), ms(7));
+        // process 2 is further left now
+        assert!(cfs.preempt(&p));
+    }
+
+    #[test]
+    fn newcomers_bring_no_credit() {
+        let mut cfs = Cfs::default();
+        cfs.on_enqueue(pcb(1, |_| {}));
+        run(&mut cfs, ms(100), 3);
+
+        cfs.on_enqueue(pcb(2, |_| {}));
+        let vruntime = |id| {
+            let (_, (p, _)) = cfs.run_queue.iter().find(|(k, _)| k.1 == id).unwrap();
+            p.pinfo.vruntime
+        };
+
+        // half the latency behind, not 300ms
+        assert_eq!(vruntime(1), ms(300));
+        assert_eq!(vruntime(2), ms(300) - ms(12));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::pcb::Pcb;
+use crate::policy::SchedulingPolicy;
+use crate::rng::Rng;
+
+/// Lottery scheduling: every pick draws a random ticket among all the
+/// tickets of the queued processes.
+pub struct Lottery {
+    queue: Vec<Pcb>,
+    rng: Rng,
+}
+
+impl Lottery {
+    pub fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+            rng: Rng::new(0),
+        }
+    }
+}
+
+impl Default for Lottery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulingPolicy for Lottery {
+    fn name(&self) -> &'static str {
+        "lottery"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self::new())
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        if self.queue.is_empty() {
+            return None;
+        }
+
+        let total: u64 = self
+            .queue
+            .iter()
+            .map(|pcb| pcb.pinfo.tickets() as u64)
+            .sum();
+        let mut winner = self.rng.gen(0..total);
+        let idx = self
+            .queue
+            .iter()
+            .position(|pcb| {
+                let tickets = pcb.pinfo.tickets() as u64;
+
+                if winner < tickets {
+                    true
+                } else {
+                    winner -= tickets;
+                    false
+                }
+            })
+            .unwrap_or(self.queue.len() - 1);
+
+        Some(self.queue.swap_remove(idx))
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        self.queue.push(pcb);
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+    use crate::policy::tests::run;
+    use std::time::Duration;
+
+    fn lottery(seed: u64) -> Vec<u32> {
+        let mut lottery = Lottery::new();
+
+        lottery.seed(seed);
+        lottery.on_enqueue(pcb(1, |p| p.set_tickets(100)));
+        lottery.on_enqueue(pcb(2, |p| p.set_tickets(300)));
+        run(&mut lottery, Duration::from_millis(1), 4000)
+    }
+
+    #[test]
+    fn wins_in_proportion_to_tickets() {
+        let picked = lottery(7);
+        let first = picked.iter().filter(|&&id| id == 1).count();
+
+        assert!((900..1100).contains(&first), "{}", first);
+        assert_eq!(picked, lottery(7));
+    }
+}
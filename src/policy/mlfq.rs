@@ -0,0 +1,156 @@
+use crate::clock;
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use crate::policy::SchedulingPolicy;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Multi-Level Feedback Queue.
+///
+/// Level 0 is the highest priority, the quantum doubles on every level below.
+/// A process which uses its whole quantum in one poll is demoted, one which
+/// blocks on a file op keeps its level, and every `boost_interval` all
+/// processes go back to level 0.
+pub struct Mlfq {
+    queues: Vec<VecDeque<Pcb>>,
+    time_slice: Duration,
+    boost_interval: Duration,
+}
+
+impl Mlfq {
+    pub fn new(levels: usize, time_slice: Duration, boost_interval: Duration) -> Self {
+        assert!(levels > 0, "MLFQ needs at least one level");
+
+        Self {
+            queues: (0..levels).map(|_| VecDeque::new()).collect(),
+            time_slice,
+            boost_interval,
+        }
+    }
+
+    fn level_quantum(&self, level: usize) -> Duration {
+        self.time_slice * (1 << level)
+    }
+
+    fn boost_epoch(&self) -> u32 {
+        (clock::now().as_nanos() / self.boost_interval.as_nanos().max(1)) as u32
+    }
+}
+
+impl Default for Mlfq {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(20), Duration::from_secs(1))
+    }
+}
+
+impl SchedulingPolicy for Mlfq {
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self {
+            queues: (0..self.queues.len()).map(|_| VecDeque::new()).collect(),
+            time_slice: self.time_slice,
+            boost_interval: self.boost_interval,
+        })
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        self.queues.iter_mut().find_map(|q| q.pop_front())
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        let epoch = self.boost_epoch();
+        let pinfo = pcb.pinfo_mut();
+
+        // boost lazily: whoever has not seen the current epoch yet is moved up
+        if pinfo.boost_epoch != epoch {
+            pinfo.boost_epoch = epoch;
+            pinfo.level = Some(0);
+        }
+
+        let level = pinfo.level.unwrap_or(0).min(self.queues.len() - 1);
+        pinfo.level = Some(level);
+        self.queues[level].push_back(pcb);
+    }
+
+    fn quantum(&self, pinfo: &PInfo, _time_slice: Duration) -> Duration {
+        self.level_quantum(pinfo.level.unwrap_or(0))
+    }
+
+    fn on_tick(&mut self, pcb: &Pcb, used: Duration) {
+        let pinfo = pcb.pinfo_mut();
+        let level = pinfo.level.unwrap_or(0);
+
+        if used >= self.level_quantum(level) && level + 1 < self.queues.len() {
+            pinfo.level = Some(level + 1);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+    use crate::policy::tests::run;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// No boost within a test.
+    fn mlfq() -> Mlfq {
+        Mlfq::new(3, ms(10), Duration::from_secs(1 << 30))
+    }
+
+    #[test]
+    fn demotes_on_a_whole_quantum() {
+        let mut mlfq = mlfq();
+        mlfq.on_enqueue(pcb(1, |_| {}));
+        mlfq.on_enqueue(pcb(2, |_| {}));
+
+        assert_eq!(run(&mut mlfq, ms(10), 4), [1, 2, 1, 2]);
+        // 10ms is not the whole 20ms quantum of level 1
+        let levels: Vec<_> = mlfq.queues[1].iter().map(|p| p.pinfo.level).collect();
+        assert_eq!(levels, [Some(1), Some(1)]);
+
+        // a new process runs first, on level 0
+        mlfq.on_enqueue(pcb(3, |_| {}));
+        assert_eq!(run(&mut mlfq, ms(5), 3), [3, 3, 3]);
+    }
+
+    #[test]
+    fn quantum_doubles_per_level() {
+        let mlfq = mlfq();
+        let quantum = |level| {
+            let p = pcb(1, |p| p.level = Some(level));
+            mlfq.quantum(p.pinfo, ms(100))
+        };
+
+        assert_eq!(
+            [quantum(0), quantum(1), quantum(2)],
+            [ms(10), ms(20), ms(40)]
+        );
+    }
+
+    #[test]
+    fn boosts_once_per_epoch() {
+        let mut mlfq = mlfq();
+        mlfq.on_enqueue(pcb(1, |p| p.level = Some(2)));
+        // last seen before the current epoch
+        mlfq.on_enqueue(pcb(2, |p| {
+            p.level = Some(2);
+            p.boost_epoch = u32::MAX;
+        }));
+
+        let picked: Vec<_> = std::iter::from_fn(|| mlfq.pick_next())
+            .map(|p| (p.pinfo.id, p.pinfo.level))
+            .collect();
+        assert_eq!(picked, [(2, Some(0)), (1, Some(2))]);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::pcb::Pcb;
+use crate::pcb::RtTask;
+use crate::policy::Queued;
+use crate::policy::SchedulingPolicy;
+use std::collections::BTreeMap;
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+/// Admitted real-time processes ordered by `key`, everything else runs in
+/// the background by `Metric`, like `PriorityPolicy`.
+struct RtQueue<K> {
+    key: fn(&RtTask) -> K,
+    rt: BTreeMap<(K, u32), Pcb>,
+    best_effort: BinaryHeap<Queued>,
+}
+
+impl<K: Ord + Copy> RtQueue<K> {
+    fn new(key: fn(&RtTask) -> K) -> Self {
+        Self {
+            key,
+            rt: BTreeMap::new(),
+            best_effort: BinaryHeap::new(),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Pcb> {
+        match self.rt.pop_first() {
+            Some((_, pcb)) => Some(pcb),
+            None => Queued::pop(&mut self.best_effort),
+        }
+    }
+
+    fn push(&mut self, pcb: Pcb) {
+        match &pcb.pinfo.rt {
+            Some(rt) if rt.admitted => {
+                self.rt.insert(((self.key)(rt), pcb.pinfo.id), pcb);
+            }
+            _ => self.best_effort.push(Queued::new(pcb)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.rt.len() + self.best_effort.len()
+    }
+}
+
+/// The total and the highest of `utilizations`.
+fn total_max(utilizations: &[f64]) -> (f64, f64) {
+    let total = utilizations.iter().sum();
+    let max = utilizations.iter().copied().fold(0.0, f64::max);
+
+    (total, max)
+}
+
+/// Earliest Deadline First: the job with the nearest absolute deadline runs.
+pub struct Edf(RtQueue<Duration>);
+
+impl Edf {
+    pub fn new() -> Self {
+        Self(RtQueue::new(RtTask::abs_deadline))
+    }
+}
+
+impl Default for Edf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulingPolicy for Edf {
+    fn name(&self) -> &'static str {
+        "edf"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self::new())
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        self.0.pop()
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        self.0.push(pcb);
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Goossens, Funk & Baruah, for global EDF: U <= m(1 - u_max) + u_max.
+    /// `U <= m` alone does not do, as one heavy task may miss its deadlines
+    /// behind light ones on every worker (Dhall's effect).
+    fn admit(&self, utilizations: &[f64], workers: usize) -> bool {
+        let (total, max) = total_max(utilizations);
+        let m = workers as f64;
+
+        total <= m * (1.0 - max) + max
+    }
+}
+
+/// Rate Monotonic: fixed priorities, the shorter the period the higher.
+pub struct Rm(RtQueue<Duration>);
+
+impl Rm {
+    pub fn new() -> Self {
+        Self(RtQueue::new(|rt| rt.period))
+    }
+}
+
+impl Default for Rm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulingPolicy for Rm {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self::new())
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        self.0.pop()
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        self.0.push(pcb);
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// On one worker Liu & Layland: U <= n(2^(1/n) - 1). On more, global RM
+    /// by Bertogna, Cirinei & Lipari: U <= m/2 (1 - u_max) + u_max.
+    fn admit(&self, utilizations: &[f64], workers: usize) -> bool {
+        let (total, max) = total_max(utilizations);
+        let (n, m) = (utilizations.len() as f64, workers as f64);
+
+        if utilizations.is_empty() {
+            return true;
+        }
+        match workers {
+            1 => total <= n * (2f64.powf(1.0 / n) - 1.0),
+            _ => total <= m / 2.0 * (1.0 - max) + max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Process `id` with a job released at `release`.
+    fn task(id: u32, period: u64, deadline: u64, release: u64, admitted: bool) -> Pcb {
+        pcb(id, |p| {
+            p.rt = Some(RtTask {
+                period: ms(period),
+                wcet: ms(1),
+                deadline: ms(deadline),
+                release: ms(release),
+                jobs: 0,
+                misses: 0,
+                admitted,
+            })
+        })
+    }
+
+    fn order(policy: &mut dyn SchedulingPolicy) -> Vec<u32> {
+        policy.on_enqueue(task(1, 10, 10, 20, true));
+        policy.on_enqueue(task(2, 50, 15, 0, true));
+        policy.on_enqueue(task(3, 20, 20, 0, true));
+        // rejected by the admission test, so best effort
+        policy.on_enqueue(task(4, 5, 5, 0, false));
+        policy.on_enqueue(pcb(5, |p| p.metric.priority = 1));
+
+        std::iter::from_fn(|| policy.pick_next())
+            .map(|p| p.pinfo.id)
+            .collect()
+    }
+
+    #[test]
+    fn edf_by_absolute_deadline() {
+        assert_eq!(order(&mut Edf::new()), [2, 3, 1, 5, 4]);
+    }
+
+    #[test]
+    fn rm_by_period() {
+        assert_eq!(order(&mut Rm::new()), [1, 3, 2, 5, 4]);
+    }
+
+    #[test]
+    fn admission_tests() {
+        let edf = Edf::new();
+        assert!(edf.admit(&[0.5, 0.5], 1));
+        assert!(!edf.admit(&[0.6, 0.5], 1));
+        // 2(1 - 0.5) + 0.5 on two workers
+        assert!(edf.admit(&[0.5, 0.5, 0.4], 2));
+        // U = 1.8 <= 2, but the bound is 2(1 - 0.6) + 0.6 = 1.4
+        assert!(!edf.admit(&[0.6, 0.6, 0.6], 2));
+
+        let rm = Rm::new();
+        assert!(rm.admit(&[], 1));
+        assert!(rm.admit(&[1.0], 1));
+        // 2(√2 - 1) ≈ 0.83 for two tasks
+        assert!(rm.admit(&[0.4, 0.4], 1));
+        assert!(!rm.admit(&[0.5, 0.4], 1));
+        // 2/2 (1 - u_max) + u_max = 1 on two workers
+        assert!(rm.admit(&[0.3, 0.3, 0.3], 2));
+        assert!(!rm.admit(&[0.5, 0.3, 0.3], 2));
+    }
+}
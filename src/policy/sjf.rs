@@ -0,0 +1,245 @@
+use crate::ops::Stone;
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use crate::policy::SchedulingPolicy;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Predicts the next CPU burst (the `Stone::Time` between two blocking
+/// operations) by exponential averaging: τ' = α·t + (1 - α)·τ.
+#[derive(Clone, Copy)]
+struct Estimator {
+    alpha: f64,
+    initial: Duration,
+}
+
+impl Estimator {
+    fn predicted(&self, pinfo: &mut PInfo) -> Duration {
+        if let Some(predicted) = pinfo.burst.predicted {
+            return predicted;
+        }
+
+        // seed from the bursts finished before, if any
+        let (sum, n) = pinfo
+            .stones
+            .iter()
+            .rev()
+            .skip(1)
+            .filter_map(|stone| match stone {
+                Stone::Time(d) => Some(*d),
+                Stone::Ops(_) => None,
+            })
+            .fold((Duration::ZERO, 0), |(sum, n), d| (sum + d, n + 1));
+        let predicted = sum.checked_div(n).unwrap_or(self.initial);
+
+        pinfo.burst.predicted = Some(predicted);
+        predicted
+    }
+
+    fn end_burst(&self, pinfo: &mut PInfo) {
+        let predicted = self.predicted(pinfo);
+        let actual = pinfo.current_burst();
+        let next = actual.as_secs_f64() * self.alpha + predicted.as_secs_f64() * (1.0 - self.alpha);
+
+        pinfo.burst.error += actual.abs_diff(predicted);
+        pinfo.burst.bursts += 1;
+        pinfo.burst.predicted = Some(Duration::from_secs_f64(next));
+    }
+}
+
+struct BurstQueue {
+    estimator: Estimator,
+    queue: BTreeMap<(Duration, u32), Pcb>,
+}
+
+impl BurstQueue {
+    fn new(alpha: f64, initial: Duration) -> Self {
+        assert!((0.0..=1.0).contains(&alpha), "alpha must be in 0..=1");
+
+        Self {
+            estimator: Estimator { alpha, initial },
+            queue: BTreeMap::new(),
+        }
+    }
+
+    fn remaining(&self, pinfo: &mut PInfo) -> Duration {
+        self.estimator
+            .predicted(pinfo)
+            .saturating_sub(pinfo.current_burst())
+    }
+}
+
+/// Shortest Job First, non-preemptive: the process with the shortest
+/// predicted burst runs until it blocks or is done.
+pub struct Sjf(BurstQueue);
+
+impl Sjf {
+    pub fn new(alpha: f64, initial: Duration) -> Self {
+        Self(BurstQueue::new(alpha, initial))
+    }
+}
+
+impl Default for Sjf {
+    fn default() -> Self {
+        Self::new(0.5, Duration::from_millis(20))
+    }
+}
+
+impl SchedulingPolicy for Sjf {
+    fn name(&self) -> &'static str {
+        "sjf"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        let e = self.0.estimator;
+        Box::new(Self::new(e.alpha, e.initial))
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        self.0.queue.pop_first().map(|(_, pcb)| pcb)
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        let pinfo = pcb.pinfo_mut();
+        let predicted = self.0.estimator.predicted(pinfo);
+
+        self.0.queue.insert((predicted, pinfo.id), pcb);
+    }
+
+    fn preempt(&mut self, _pcb: &Pcb) -> bool {
+        false
+    }
+
+    fn on_block(&mut self, pcb: &Pcb) {
+        self.0.estimator.end_burst(pcb.pinfo_mut());
+    }
+
+    fn on_exit(&mut self, pcb: &Pcb) {
+        self.0.estimator.end_burst(pcb.pinfo_mut());
+    }
+
+    fn len(&self) -> usize {
+        self.0.queue.len()
+    }
+}
+
+/// Shortest Remaining Time First: SJF where the running process gives up
+/// the worker as soon as a queued one has less predicted time remaining.
+pub struct Srtf(BurstQueue);
+
+impl Srtf {
+    pub fn new(alpha: f64, initial: Duration) -> Self {
+        Self(BurstQueue::new(alpha, initial))
+    }
+}
+
+impl Default for Srtf {
+    fn default() -> Self {
+        Self::new(0.5, Duration::from_millis(20))
+    }
+}
+
+impl SchedulingPolicy for Srtf {
+    fn name(&self) -> &'static str {
+        "srtf"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        let e = self.0.estimator;
+        Box::new(Self::new(e.alpha, e.initial))
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        self.0.queue.pop_first().map(|(_, pcb)| pcb)
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        let pinfo = pcb.pinfo_mut();
+        let remaining = self.0.remaining(pinfo);
+
+        self.0.queue.insert((remaining, pinfo.id), pcb);
+    }
+
+    fn preempt(&mut self, pcb: &Pcb) -> bool {
+        let remaining = self.0.remaining(pcb.pinfo_mut());
+
+        match self.0.queue.keys().next() {
+            Some(&(shortest, _)) => shortest < remaining,
+            None => false,
+        }
+    }
+
+    fn on_block(&mut self, pcb: &Pcb) {
+        self.0.estimator.end_burst(pcb.pinfo_mut());
+    }
+
+    fn on_exit(&mut self, pcb: &Pcb) {
+        self.0.estimator.end_burst(pcb.pinfo_mut());
+    }
+
+    fn len(&self) -> usize {
+        self.0.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn order(policy: &mut dyn SchedulingPolicy) -> Vec<u32> {
+        std::iter::from_fn(|| policy.pick_next())
+            .map(|p| p.pinfo.id)
+            .collect()
+    }
+
+    #[test]
+    fn shortest_predicted_first() {
+        let mut sjf = Sjf::default();
+        sjf.on_enqueue(pcb(1, |p| p.burst.predicted = Some(ms(30))));
+        sjf.on_enqueue(pcb(2, |p| p.burst.predicted = Some(ms(10))));
+        // predicted 20ms to begin with
+        sjf.on_enqueue(pcb(3, |_| {}));
+
+        assert_eq!(order(&mut sjf), [2, 3, 1]);
+        assert!(!sjf.preempt(&pcb(4, |_| {})));
+    }
+
+    #[test]
+    fn predicts_by_exponential_average() {
+        let mut sjf = Sjf::default();
+        let p = pcb(1, |p| p.stones.push_back(Stone::Time(ms(40))));
+
+        sjf.on_block(&p);
+        assert_eq!(p.pinfo.burst.predicted, Some(ms(30)));
+        assert_eq!(p.pinfo.burst.avg_error(), ms(20));
+
+        p.pinfo_mut().stones.push_back(Stone::Time(ms(10)));
+        sjf.on_exit(&p);
+        assert_eq!(p.pinfo.burst.predicted, Some(ms(20)));
+        assert_eq!(p.pinfo.burst.avg_error(), ms(20));
+    }
+
+    #[test]
+    fn shortest_remaining_first() {
+        let mut srtf = Srtf::default();
+        srtf.on_enqueue(pcb(1, |p| p.burst.predicted = Some(ms(10))));
+        srtf.on_enqueue(pcb(2, |p| {
+            p.burst.predicted = Some(ms(30));
+            p.stones.push_back(Stone::Time(ms(25)));
+        }));
+        assert_eq!(order(&mut srtf), [2, 1]);
+
+        srtf.on_enqueue(pcb(3, |p| p.burst.predicted = Some(ms(10))));
+        // 15ms left of 20ms, more than 10ms
+        let running = pcb(4, |p| p.stones.push_back(Stone::Time(ms(5))));
+        assert!(srtf.preempt(&running));
+        // 5ms left
+        *running.pinfo_mut().stones.back_mut().unwrap() = Stone::Time(ms(15));
+        assert!(!srtf.preempt(&running));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::pcb::Pcb;
+use crate::policy::SchedulingPolicy;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const STRIDE1: u64 = 1 << 20;
+
+/// Stride scheduling: the deterministic counterpart of `Lottery`.
+///
+/// Runs the smallest pass, and advances the pass by `STRIDE1 / tickets` for
+/// every millisecond used.
+pub struct Stride {
+    queue: BTreeMap<(u64, u32), Pcb>,
+    // shared by every fork, a new or migrated process starts from here
+    global_pass: Arc<AtomicU64>,
+}
+
+impl Stride {
+    pub fn new() -> Self {
+        Self {
+            queue: BTreeMap::new(),
+            global_pass: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Default for Stride {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulingPolicy for Stride {
+    fn name(&self) -> &'static str {
+        "stride"
+    }
+
+    fn fork(&self) -> Box<dyn SchedulingPolicy> {
+        Box::new(Self {
+            queue: BTreeMap::new(),
+            global_pass: self.global_pass.clone(),
+        })
+    }
+
+    fn pick_next(&mut self) -> Option<Pcb> {
+        let ((pass, _), pcb) = self.queue.pop_first()?;
+
+        self.global_pass.fetch_max(pass, Ordering::Relaxed);
+        Some(pcb)
+    }
+
+    fn on_enqueue(&mut self, pcb: Pcb) {
+        let pinfo = pcb.pinfo_mut();
+
+        // do not let a process which slept for long monopolize the worker
+        pinfo.pass = pinfo.pass.max(self.global_pass.load(Ordering::Relaxed));
+        self.queue.insert((pinfo.pass, pinfo.id), pcb);
+    }
+
+    fn on_tick(&mut self, pcb: &Pcb, used: Duration) {
+        let pinfo = pcb.pinfo_mut();
+        let stride = STRIDE1 / pinfo.tickets() as u64;
+
+        pinfo.pass += stride * used.as_micros() as u64 / 1000;
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::tests::pcb;
+    use crate::policy::tests::run;
+
+    #[test]
+    fn runs_in_proportion_to_tickets() {
+        let mut stride = Stride::new();
+
+        stride.on_enqueue(pcb(1, |p| p.set_tickets(100)));
+        stride.on_enqueue(pcb(2, |p| p.set_tickets(300)));
+        let picked = run(&mut stride, Duration::from_millis(1), 40);
+
+        assert_eq!(picked.iter().filter(|&&id| id == 1).count(), 10);
+        // never more than 3 in a row for the one with more tickets
+        assert!(picked.windows(4).all(|w| w.contains(&1)));
+    }
+}
@@ -1,26 +1,25 @@
+use crate::clock;
 use crate::ops::OpsType;
 use crate::ops::Stone;
 use crate::pcb::PInfo;
-use crate::pcb::Pcb;
 use crate::scheduler::Share;
+use crate::util::rate::Rate;
+use crate::util::rate::RemainRate;
 use crate::worker_info::WorkerInfo;
 use core::fmt;
 use crossterm::cursor;
-use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::collections::LinkedList;
 use std::io::stdout;
-use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::Duration;
-use std::time::Instant;
-use wutil::util::rate::Rate;
-use wutil::util::rate::RemainRate;
 
 pub struct Printer {
-    start_point: Instant,
+    start_point: Duration,
     pub schedule_threads: usize,
-    pub pinfos: LinkedList<&'static PInfo>,
+    pub policy: &'static str,
+    pub seed: u64,
     pub share: &'static Share,
     pub worker_infos: Option<&'static LinkedList<WorkerInfo>>,
 }
@@ -28,10 +27,11 @@ pub struct Printer {
 impl Printer {
     pub fn new(share: &'static Share) -> Self {
         Self {
-            start_point: Instant::now(),
+            start_point: clock::now(),
             worker_infos: None,
             schedule_threads: 0,
-            pinfos: LinkedList::new(),
+            policy: "",
+            seed: 0,
             share,
         }
     }
@@ -39,7 +39,7 @@ impl Printer {
 
 pub fn print(printer: &'static Printer, d: Duration) {
     // hide the cursor
-    crossterm::execute! {
+    let _ = crossterm::execute! {
         stdout(),
         cursor::Hide,
     };
@@ -56,24 +56,27 @@ impl fmt::Display for Printer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let done = self.share.done.load(Ordering::Relaxed);
         let processes = self.share.processes.load(Ordering::Relaxed);
-        let printer_done = done == processes;
 
         writeln!(
             f,
             "☆ Cost Time: {:.1?} \n\
             ☆ Threads: {} \n\
+            ☆ Policy: {} \n\
+            ☆ Seed: {} \n\
 			☆ Compeletes : {}/{}",
-            self.start_point.elapsed(),
+            clock::now() - self.start_point,
             self.schedule_threads,
+            self.policy,
+            self.seed,
             done,
             processes,
-        );
+        )?;
 
         write!(f, "☆ Workload:")?;
         let worker_infos = self.worker_infos.unwrap();
         let mut running_threads = 0;
         for worker_info in worker_infos.iter() {
-            let running_time = worker_info.start_point.elapsed();
+            let running_time = clock::now() - worker_info.start_point;
             let remain_rate = RemainRate(
                 worker_info.waiting_time.as_secs_f64(),
                 running_time.as_secs_f64(),
@@ -108,8 +111,13 @@ impl fmt::Display for Printer {
             )
         )?;
 
-        for pinfo in &self.pinfos {
+        let pinfos = self.share.sorted_pinfos();
+
+        for (pinfo, depth) in tree(&pinfos) {
             // -------------each pinfo printing----------
+            if depth > 0 {
+                write!(f, "{}└─", "   ".repeat(depth - 1))?;
+            }
             write!(
                 f,
                 "🍀 {}. {}({} {:.1?} 🍒x{:.1}): ",
@@ -124,19 +132,51 @@ impl fmt::Display for Printer {
                 pinfo.run_slices
             )?;
 
+            if let Some(level) = pinfo.level {
+                write!(f, "L{} ", level)?;
+            }
+
+            if let Some(own) = pinfo.base_priority {
+                write!(f, "⇡{} ", own)?;
+            }
+
+            if pinfo.preemptions > 0 {
+                write!(f, "✂️{} ", pinfo.preemptions)?;
+            }
+
+            if let Some(rt) = &pinfo.rt {
+                write!(
+                    f,
+                    "⏰{}/{}{} ",
+                    rt.misses,
+                    rt.jobs,
+                    if rt.admitted { "" } else { "(rejected)" }
+                )?;
+            }
+
+            if let Some(predicted) = pinfo.burst.predicted {
+                write!(f, "τ{:.1?}±{:.1?} ", predicted, pinfo.burst.avg_error())?;
+            }
+
+            if matches!(self.policy, "lottery" | "stride") {
+                write!(f, "🎟️{} ", pinfo.tickets())?;
+            }
+
+            write!(f, "{} ", pinfo.state)?;
+
             for stone in pinfo.stones.iter() {
                 match stone {
                     Stone::Time(d) => {
                         // ☀️ = 10s 🌛 = 1s ⭐️ = 50ms 🍒 = times slice
-                        let x_min = d.div_duration_f32(Duration::from_secs(10)) as u32;
+                        let x_min = (d.as_secs_f64() / 10.0) as u32;
 
                         for _ in 0..x_min {
                             write!(f, "☀️")?;
                         }
 
-                        let x_ten_sec = (d.saturating_sub(Duration::from_secs(10) * x_min))
-                            .div_duration_f32(Duration::from_secs(1))
-                            as u32;
+                        let x_ten_sec = d
+                            .saturating_sub(Duration::from_secs(10) * x_min)
+                            .as_secs_f64() as u32;
 
                         for _ in 0..x_ten_sec {
                             write!(f, "🌛")?;
@@ -144,9 +184,9 @@ impl fmt::Display for Printer {
 
                         let x_sec = (d
                             .saturating_sub(Duration::from_secs(10) * x_min)
-                            .saturating_sub(Duration::from_secs(1) * x_ten_sec))
-                        .div_duration_f32(Duration::from_millis(50))
-                            as u32;
+                            .saturating_sub(Duration::from_secs(1) * x_ten_sec)
+                            .as_secs_f64()
+                            / 0.05) as u32;
 
                         for _ in 0..x_sec {
                             write!(f, "⭐️")?;
@@ -156,9 +196,6 @@ impl fmt::Display for Printer {
                         OpsType::File => {
                             write!(f, "📃")?;
                         }
-                        OpsType::Net => {
-                            write!(f, "🌏")?;
-                        }
                     },
                 }
             }
@@ -168,10 +205,44 @@ impl fmt::Display for Printer {
             writeln!(f)?;
         }
 
-        if self.share.scheduler_done.load(Ordering::Relaxed) && printer_done {
+        // processes still blocked then never finish
+        if self.share.scheduler_done.load(Ordering::Relaxed) {
+            let (misses, jobs) = pinfos
+                .iter()
+                .filter_map(|pinfo| pinfo.rt.as_ref())
+                .fold((0, 0), |(m, j), rt| (m + rt.misses, j + rt.jobs));
+
+            if jobs > 0 {
+                writeln!(f, "\n☆ Deadline Misses: {}/{}", misses, jobs)?;
+            }
             writeln!(f, "\n\nALL DONE!")?;
             self.share.printer_done.store(true, Ordering::Relaxed);
         }
         Ok(())
     }
 }
+
+/// The processes in depth-first order of the process tree, children after
+/// their parent, with their depth in it.
+fn tree(pinfos: &[&'static PInfo]) -> Vec<(&'static PInfo, usize)> {
+    let mut children: HashMap<u32, Vec<&'static PInfo>> = HashMap::new();
+    let mut stack = Vec::new();
+
+    for pinfo in pinfos.iter().rev() {
+        match pinfo.parent {
+            Some(parent) if pinfos.iter().any(|p| p.id == parent) => {
+                children.entry(parent).or_default().push(pinfo)
+            }
+            _ => stack.push((*pinfo, 0)),
+        }
+    }
+
+    let mut order = Vec::with_capacity(pinfos.len());
+    while let Some((pinfo, depth)) = stack.pop() {
+        order.push((pinfo, depth));
+        for child in children.remove(&pinfo.id).unwrap_or_default() {
+            stack.push((child, depth + 1));
+        }
+    }
+    order
+}
@@ -1,28 +1,21 @@
+use crate::clock;
+use crate::event::MsgTx;
+use crate::event::SimEvent;
 use crate::fs::FileOp;
 use crate::fs::TXTS;
 use crate::ops::Op;
 use crate::ops::OpsRes;
-use crate::pcb::PInfo;
 use crate::pcb::PollRes;
 use crate::pcb::Process;
-use crossbeam_channel::Sender;
-use rg::extend::Case;
-use rg::fmt::json::Json;
-use rg::Mode;
-use rg::Rg;
+use crate::rng;
+use crate::rng::Rng;
+use crate::util::convert::StaticRef;
+use crate::util::types::SS;
 use std::borrow::Cow;
 use std::fmt::Write;
-use std::ops::Range;
 use std::ops::RangeInclusive;
 use std::path::Path;
-use std::thread;
 use std::time::Duration;
-use wutil::convert::static_ref_mut;
-use wutil::convert::StaticRef;
-use wutil::convert::StaticRefArray;
-use wutil::random::gen;
-use wutil::types::SStr;
-use wutil::types::SS;
 
 #[macro_export]
 macro_rules! process {
@@ -41,7 +34,7 @@ macro_rules! process {
 				&self.1
 			}
 
-			fn poll(&mut self, _: Sender<SStr>, _ops_res: OpsRes) -> $crate::processes::PollRes {
+			fn poll(&mut self, _: $crate::event::MsgTx, _ops_res: OpsRes) -> $crate::processes::PollRes {
 				use $crate::processes::PollRes;
 
 				self.0 += 1;
@@ -65,12 +58,12 @@ process!(P2, "P2",,, {PollRes::Ready});
 process!(P3, "P3", 1;2,
 {
 
-    thread::sleep(Duration::from_millis(100));
+    clock::burn(Duration::from_millis(100));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(50));
+    clock::burn(Duration::from_millis(50));
     PollRes::Polling(Op::None)
 },
 {PollRes::Ready}
@@ -78,17 +71,17 @@ process!(P3, "P3", 1;2,
 process!(P4, "P4", 1;2;3,
 {
 
-    thread::sleep(Duration::from_millis(100));
+    clock::burn(Duration::from_millis(100));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(100));
+    clock::burn(Duration::from_millis(100));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(2000));
+    clock::burn(Duration::from_millis(2000));
     PollRes::Polling(Op::None)
 },
 {PollRes::Ready}
@@ -96,22 +89,22 @@ process!(P4, "P4", 1;2;3,
 process!(P5, "P5", 1;2;3;4,
 {
 
-    thread::sleep(Duration::from_millis(100));
+    clock::burn(Duration::from_millis(100));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(100));
+    clock::burn(Duration::from_millis(100));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(2000));
+    clock::burn(Duration::from_millis(2000));
     PollRes::Polling(Op::None)
 };
 {
 
-    thread::sleep(Duration::from_millis(400));
+    clock::burn(Duration::from_millis(400));
     PollRes::Polling(Op::None)
 },
 {PollRes::Ready}
@@ -137,22 +130,22 @@ impl Process for P1 {
         &self.name
     }
 
-    fn poll(&mut self, s: Sender<SStr>, ops_res: OpsRes) -> crate::pcb::PollRes {
+    fn poll(&mut self, _: MsgTx, _: OpsRes) -> crate::pcb::PollRes {
         self.n += 1;
 
         match self.n {
             1 => {
-                thread::sleep(Duration::from_millis(40));
+                clock::burn(Duration::from_millis(40));
                 PollRes::Polling(Op::AddPriority(10))
             }
             2 => {
-                thread::sleep(Duration::from_millis(30));
+                clock::burn(Duration::from_millis(30));
                 PollRes::Polling(Op::FileOp(FileOp::Read(SS::SPath(Cow::Borrowed(
                     Path::new("file_open"),
                 )))))
             }
             3 => {
-                thread::sleep(Duration::from_millis(30));
+                clock::burn(Duration::from_millis(30));
                 PollRes::Polling(Op::None)
             }
             _ => PollRes::Ready,
@@ -160,32 +153,87 @@ impl Process for P1 {
     }
 }
 
+/// A periodic real-time process, each poll is one job taking up to `wcet`,
+/// released once per `period`.
+#[derive(Debug)]
+pub struct PeriodicProcess {
+    name: String,
+    rng: Rng,
+    period: Duration,
+    wcet: Duration,
+    /// Of the first job: it is executed right away.
+    release: Duration,
+    job: u32,
+    jobs: u32,
+}
+
+impl PeriodicProcess {
+    pub fn new(name: &str, period: Duration, wcet: Duration, jobs: u32, rng: Rng) -> Self {
+        Self {
+            name: name.to_string(),
+            rng,
+            period,
+            wcet,
+            release: clock::now(),
+            job: 0,
+            jobs,
+        }
+    }
+}
+
+impl Process for PeriodicProcess {
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    fn poll(&mut self, _: MsgTx, _ops_res: OpsRes) -> PollRes {
+        if self.job == self.jobs {
+            return PollRes::Ready;
+        }
+
+        clock::burn(self.rng.gen(self.wcet / 2..=self.wcet));
+        self.job += 1;
+
+        if self.job == self.jobs {
+            return PollRes::Polling(Op::None);
+        }
+        // off the CPU until the next job is released
+        PollRes::Polling(Op::SleepUntil(self.release + self.period * self.job))
+    }
+
+    fn period(&self) -> Option<Duration> {
+        Some(self.period)
+    }
+
+    fn wcet(&self) -> Option<Duration> {
+        Some(self.wcet)
+    }
+}
+
 #[derive(Debug)]
 pub struct RandomProcess {
     state: u32,
     state_max: u32,
     sleep_range: RangeInclusive<Duration>,
     name: String,
-    rg: Rg<'static>,
+    rng: Rng,
     buf: String,
-    json: Json,
 }
 
 impl RandomProcess {
-    pub fn new(state_max: u32, sleep_range: RangeInclusive<Duration>) -> Self {
+    pub fn new(state_max: u32, sleep_range: RangeInclusive<Duration>, rng: Rng) -> Self {
         Self {
             state_max,
             sleep_range,
             name: "Random Ready".to_string(),
             state: 0,
-            rg: Rg::new(),
+            rng,
             buf: String::new(),
-            json: Json::new(),
         }
     }
     fn write_name(&mut self, cur_sleep_range: Duration) {
         self.name.clear();
-        write!(
+        let _ = write!(
             &mut self.name,
             "[{}-{:.0?} {}]",
             self.state + 1,
@@ -200,76 +248,85 @@ impl Process for RandomProcess {
         &self.name
     }
 
-    fn poll(&mut self, msg_tx: Sender<SStr>, ops_res: OpsRes) -> PollRes {
+    fn poll(&mut self, msg_tx: MsgTx, ops_res: OpsRes) -> PollRes {
         match ops_res {
-            OpsRes::Empty => {}
             OpsRes::FileReadRes(r) => match r {
-                crate::ops::FileReadRes::Ok(n) => {
-                    msg_tx.send(Cow::Owned(format!("{} READ: {}", self.name, self.buf)));
+                crate::ops::FileReadRes::Ok => {
+                    msg_tx.send(
+                        &self.name,
+                        SimEvent::FileRead {
+                            content: self.buf.clone(),
+                        },
+                    );
                 }
                 crate::ops::FileReadRes::FileBufReturnNone => {}
                 crate::ops::FileReadRes::Err(e) => {
-                    msg_tx.send(Cow::Owned(format!("{} READ ERR: {}", self.name, e)));
+                    msg_tx.send(&self.name, SimEvent::FileReadErr { err: e.to_string() });
                 }
             },
             OpsRes::FileWriteRes(w) => match w {
                 crate::ops::FileWriteRes::Ok { path } => {
-                    msg_tx.send(Cow::Owned(format!("{} [{}] WRITE OK.", self.name, path)));
+                    msg_tx.send(&self.name, SimEvent::FileWriteOk { path });
                 }
                 crate::ops::FileWriteRes::Err { path, err } => {
-                    msg_tx.send(Cow::Owned(format!(
-                        "{} [{path}] WRITE ERR: {}",
-                        self.name, err
-                    )));
+                    msg_tx.send(
+                        &self.name,
+                        SimEvent::FileWriteErr {
+                            path,
+                            err: err.to_string(),
+                        },
+                    );
                 }
             },
+            // it never spawns, waits nor receives
+            _ => {}
         }
 
         if self.state == self.state_max {
             return PollRes::Ready;
         }
-        let cur_sleep_range = gen(self.sleep_range.clone());
+        let cur_sleep_range = self.rng.gen(self.sleep_range.clone());
 
         self.write_name(cur_sleep_range);
 
         /* probably send a msg to print */
-        let print_choice = gen(0..10000_i32);
+        let print_choice = self.rng.gen(0..10000_i32);
 
         if print_choice == 0 {
-            msg_tx.send(Cow::Owned(format!(
-                "{} say: {}",
-                self.name,
-                self.rg.once::<&str, _>(Mode::ASLP(","))
-            )));
+            msg_tx.send(
+                &self.name,
+                SimEvent::Said {
+                    text: self.rng.words(","),
+                },
+            );
         }
 
         /* sleep for heavy work */
-        thread::sleep(cur_sleep_range);
+        clock::burn(cur_sleep_range);
 
         self.state += 1;
 
-        let op_choice = gen(0..10000_i32);
+        let op_choice = self.rng.gen(0..10000_i32);
         match op_choice {
-            0 => PollRes::Polling(Op::AddPriority(gen(1..=30))),
-            1 => PollRes::Polling(Op::SubPriority(gen(1..=30))),
-            2 => PollRes::Polling(Op::SetPriority(gen(1..=30))),
+            0 => PollRes::Polling(Op::AddPriority(self.rng.gen(1..=30))),
+            1 => PollRes::Polling(Op::SubPriority(self.rng.gen(1..=30))),
+            2 => PollRes::Polling(Op::SetPriority(self.rng.gen(1..=30))),
             3 => PollRes::Polling(Op::FileOp(FileOp::Read({
-                unsafe {
-                    let choice = gen(0..TXTS.len() + 2);
+                let txts = TXTS.lock();
+                let choice = self.rng.gen(0..txts.len() + 2);
 
-                    if choice < TXTS.len() {
-                        SS::SPath(Cow::Borrowed(TXTS.get_unchecked(choice)))
-                    } else {
-                        SS::SStr(self.rg.once::<&str, _>(Mode::ASLP(",")))
-                    }
+                if choice < txts.len() {
+                    SS::SPath(Cow::Owned(txts[choice].clone()))
+                } else {
+                    SS::SStr(Cow::Owned(self.rng.words(",")))
                 }
             }))),
             4 => PollRes::Polling(Op::FileOp(FileOp::Write {
-                path: format!("out/{}.txt", self.rg.word(3..=10, Case::Lower)),
+                path: format!("out/{}.txt", self.rng.word(3..=10)),
                 content: {
-                    match gen(0..10) {
-                        0 => Cow::Owned(self.json.generate()),
-                        _ => self.rg.once::<&str, _>(Mode::ASLP(",")),
+                    match self.rng.gen(0..10) {
+                        0 => Cow::Owned(self.rng.json()),
+                        _ => Cow::Owned(self.rng.words(",")),
                     }
                 },
             })),
@@ -288,14 +345,16 @@ pub struct RandomFactory {
 }
 
 impl RandomFactory {
-    pub fn new(size: usize) -> Self {
+    pub fn new(size: usize, seed: u64) -> Self {
+        let mut rng = Rng::stream(seed, rng::FACTORY);
         let mut vec = Vec::with_capacity(size);
 
         for _ in 0..size {
             vec.push(RandomProcess::new(
-                gen(0..=10000),
-                gen(Duration::from_millis(0)..=Duration::from_millis(15))
-                    ..=gen(Duration::from_millis(15)..Duration::from_millis(30)),
+                rng.gen(0..=10000),
+                rng.gen(Duration::from_millis(0)..=Duration::from_millis(15))
+                    ..=rng.gen(Duration::from_millis(15)..Duration::from_millis(30)),
+                Rng::new(rng.next_u64()),
             ));
         }
         Self { vec, idx: 0 }
@@ -323,5 +382,47 @@ impl Iterator for &'_ mut RandomFactory {
     }
 }
 
-#[test]
-fn test() {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::simulate_processes;
+
+    /// The deadline misses and jobs of each task, run under RM on one CPU.
+    fn misses(tasks: &[(u64, u64, u32)]) -> Vec<(u32, u32)> {
+        let pinfos = simulate_processes("rm", 1, || {
+            tasks
+                .iter()
+                .enumerate()
+                .map(|(i, &(period, wcet, jobs))| {
+                    Box::new(PeriodicProcess::new(
+                        &format!("T{}", i),
+                        Duration::from_millis(period),
+                        Duration::from_millis(wcet),
+                        jobs,
+                        Rng::new(i as u64),
+                    )) as Box<dyn Process>
+                })
+                .collect()
+        });
+
+        pinfos
+            .iter()
+            .map(|pinfo| pinfo.rt.as_ref().map(|rt| (rt.misses, rt.jobs)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn schedulable_tasks_miss_nothing() {
+        assert_eq!(misses(&[(10, 3, 20), (20, 5, 10)]), [(0, 20), (0, 10)]);
+    }
+
+    #[test]
+    fn overloaded_tasks_miss_deadlines() {
+        // rejected by the admission test, they run as best effort
+        let misses = misses(&[(10, 10, 20), (20, 20, 10)]);
+
+        assert!(misses.iter().all(|&(m, _)| m > 0), "{:?}", misses);
+        assert_eq!(misses[0].1, 20);
+        assert_eq!(misses[1].1, 10);
+    }
+}
@@ -0,0 +1,657 @@
+use crate::clock;
+use crate::gantt;
+use crate::locks::LockKind;
+use crate::locks::LockReport;
+use crate::pcb::PInfo;
+use crate::pcb::ProcState;
+use crate::scheduler::Share;
+use crate::trace;
+use crate::worker_info::WorkerInfo;
+use core::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct ProcessReport {
+    pub id: u32,
+    pub name: String,
+    pub arrival: Duration,
+    pub finish: Option<Duration>,
+    /// From arrival to finish.
+    pub turnaround: Option<Duration>,
+    /// Time spent in a ready queue.
+    pub waiting: Duration,
+    /// From arrival to the first poll.
+    pub response: Option<Duration>,
+    pub cpu: Duration,
+    pub exit_code: i32,
+    /// Messages received by `Op::Recv`.
+    pub received: u32,
+    /// The average time from `Op::Send` to the receiver running with it.
+    pub msg_latency: Option<Duration>,
+    /// The jobs a periodic process released, and the deadlines they missed.
+    pub jobs: u32,
+    pub misses: u32,
+    /// The mean absolute error of the SJF/SRTF burst predictions, `None`
+    /// without any.
+    pub predict_error: Option<Duration>,
+}
+
+/// The standard scheduling metrics of a run, the numbers to compare
+/// policies on.
+pub struct Report {
+    pub policy: String,
+    pub processes: Vec<ProcessReport>,
+    /// From the first arrival to the last finish.
+    pub makespan: Duration,
+    /// Finished processes per second of makespan.
+    pub throughput: f64,
+    /// The busy fraction of each worker.
+    pub utilization: Vec<(usize, f64)>,
+    /// Every simulated mutex and semaphore used.
+    pub locks: Vec<LockReport>,
+    /// The processes deadlocked, or blocked with nothing left to wake them,
+    /// when the run ended, with what they block on.
+    pub blocked: Vec<(u32, String)>,
+}
+
+/// Where to write the results of a run.
+#[derive(Default)]
+pub struct Exports {
+    pub report_csv: Option<PathBuf>,
+    pub gantt: Option<PathBuf>,
+    pub trace: Option<PathBuf>,
+}
+
+impl ProcessReport {
+    pub fn new(pinfo: &PInfo) -> Self {
+        let first = |state| pinfo.first(state);
+        // a process may be executed ahead of its arrival
+        let arrival = first(ProcState::Ready).unwrap_or_default();
+        let finish = first(ProcState::Zombie);
+
+        Self {
+            id: pinfo.id,
+            name: pinfo.name.cloned().unwrap_or_default(),
+            arrival,
+            finish,
+            turnaround: finish.map(|t| t - arrival),
+            waiting: pinfo.time_in(ProcState::Ready),
+            response: first(ProcState::Running).map(|t| t - arrival),
+            cpu: pinfo.metric.running_time,
+            exit_code: pinfo.exit_code,
+            received: pinfo.received,
+            msg_latency: pinfo.msg_latency.checked_div(pinfo.received),
+            jobs: pinfo.rt.as_ref().map_or(0, |rt| rt.jobs),
+            misses: pinfo.rt.as_ref().map_or(0, |rt| rt.misses),
+            predict_error: pinfo.burst.error.checked_div(pinfo.burst.bursts),
+        }
+    }
+}
+
+impl Report {
+    pub fn new<'a>(
+        policy: &str,
+        pinfos: impl IntoIterator<Item = &'a PInfo>,
+        locks: Vec<LockReport>,
+        blocked: Vec<(u32, String)>,
+        workers: impl IntoIterator<Item = &'a WorkerInfo>,
+    ) -> Self {
+        let now = clock::now();
+        let utilization = workers
+            .into_iter()
+            .map(|w| {
+                let elapsed = (now - w.start_point).as_secs_f64();
+                let busy = elapsed - w.waiting_time.as_secs_f64();
+
+                (w.id, busy / elapsed.max(f64::EPSILON))
+            })
+            .collect();
+
+        Self {
+            locks,
+            blocked,
+            ..Self::from_processes(
+                policy.to_string(),
+                pinfos.into_iter().map(ProcessReport::new).collect(),
+                utilization,
+            )
+        }
+    }
+
+    fn from_processes(
+        policy: String,
+        mut processes: Vec<ProcessReport>,
+        utilization: Vec<(usize, f64)>,
+    ) -> Self {
+        processes.sort_by_key(|p| p.id);
+
+        let start = processes
+            .iter()
+            .map(|p| p.arrival)
+            .min()
+            .unwrap_or_default();
+        let end = processes
+            .iter()
+            .filter_map(|p| p.finish)
+            .max()
+            .unwrap_or(start);
+        let makespan = end - start;
+        let finished = processes.iter().filter(|p| p.finish.is_some()).count();
+
+        Self {
+            policy,
+            throughput: finished as f64 / makespan.as_secs_f64().max(f64::EPSILON),
+            makespan,
+            utilization,
+            processes,
+            locks: Vec::new(),
+            blocked: Vec::new(),
+        }
+    }
+
+    fn avg(&self, f: impl Fn(&ProcessReport) -> Option<Duration>) -> Duration {
+        let (sum, n) = self
+            .processes
+            .iter()
+            .filter_map(f)
+            .fold((Duration::ZERO, 0), |(sum, n), d| (sum + d, n + 1));
+
+        sum.checked_div(n).unwrap_or_default()
+    }
+
+    pub fn avg_turnaround(&self) -> Duration {
+        self.avg(|p| p.turnaround)
+    }
+
+    pub fn avg_waiting(&self) -> Duration {
+        self.avg(|p| Some(p.waiting))
+    }
+
+    pub fn avg_response(&self) -> Duration {
+        self.avg(|p| p.response)
+    }
+
+    /// Over every message received, `None` without any.
+    pub fn avg_msg_latency(&self) -> Option<Duration> {
+        let (sum, n) = self
+            .processes
+            .iter()
+            .filter_map(|p| Some((p.msg_latency? * p.received, p.received)))
+            .fold((Duration::ZERO, 0), |(sum, n), (d, m)| (sum + d, n + m));
+
+        sum.checked_div(n)
+    }
+
+    /// The deadline misses and jobs of every periodic process, `None`
+    /// without any job.
+    pub fn deadline_misses(&self) -> Option<(u32, u32)> {
+        let (misses, jobs) = self
+            .processes
+            .iter()
+            .fold((0, 0), |(m, j), p| (m + p.misses, j + p.jobs));
+
+        (jobs > 0).then_some((misses, jobs))
+    }
+
+    /// Read back what `write_csv` wrote, named after the file. The CPU
+    /// utilization and the locks are not in there.
+    pub fn read_csv(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |line: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: not a report row", path.display(), line + 1),
+            )
+        };
+        let ms = |f: &str| -> Result<Option<Duration>, ()> {
+            match f {
+                "" => Ok(None),
+                f => f
+                    .parse::<f64>()
+                    .map(|ms| Some(Duration::from_secs_f64(ms / 1e3)))
+                    .map_err(|_| ()),
+            }
+        };
+
+        let mut processes = Vec::new();
+
+        for (n, line) in text.lines().enumerate().skip(1) {
+            // the name is the only quoted field, and may hold commas
+            let (id, rest) = line.split_once(",\"").ok_or_else(|| invalid(n))?;
+            let (name, rest) = rest.rsplit_once("\",").ok_or_else(|| invalid(n))?;
+            let rest: Vec<_> = rest.split(',').collect();
+
+            if rest.len() != 12 {
+                return Err(invalid(n));
+            }
+            let f = rest[..6]
+                .iter()
+                .chain(&rest[8..9])
+                .chain(&rest[11..])
+                .map(|f| ms(f))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(n))?;
+
+            processes.push(ProcessReport {
+                id: id.parse().map_err(|_| invalid(n))?,
+                name: name.replace("\"\"", "\""),
+                arrival: f[0].unwrap_or_default(),
+                finish: f[1],
+                turnaround: f[2],
+                waiting: f[3].unwrap_or_default(),
+                response: f[4],
+                cpu: f[5].unwrap_or_default(),
+                exit_code: rest[6].parse().map_err(|_| invalid(n))?,
+                received: rest[7].parse().map_err(|_| invalid(n))?,
+                msg_latency: f[6],
+                jobs: rest[9].parse().map_err(|_| invalid(n))?,
+                misses: rest[10].parse().map_err(|_| invalid(n))?,
+                predict_error: f[7],
+            });
+        }
+
+        let policy = path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+
+        Ok(Self::from_processes(policy, processes, Vec::new()))
+    }
+
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "id,name,arrival_ms,finish_ms,turnaround_ms,waiting_ms,response_ms,cpu_ms,exit_code,received,msg_latency_ms,jobs,misses,predict_error_ms"
+        )?;
+
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.3}", d.as_secs_f64() * 1000.0),
+            None => String::new(),
+        };
+
+        for p in &self.processes {
+            writeln!(
+                w,
+                "{},\"{}\",{},{},{},{},{},{},{},{},{},{},{},{}",
+                p.id,
+                p.name.replace('"', "\"\""),
+                ms(Some(p.arrival)),
+                ms(p.finish),
+                ms(p.turnaround),
+                ms(Some(p.waiting)),
+                ms(p.response),
+                ms(Some(p.cpu)),
+                p.exit_code,
+                p.received,
+                ms(p.msg_latency),
+                p.jobs,
+                p.misses,
+                ms(p.predict_error),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Exports {
+    pub fn write(&self, report: &Report, share: &Share) {
+        if let Some(path) = &self.report_csv {
+            warn(path, File::create(path).and_then(|f| report.write_csv(f)));
+        }
+        if let Some(path) = &self.gantt {
+            warn(path, gantt::export(share, path));
+        }
+        if let Some(path) = &self.trace {
+            warn(path, trace::export(share, path));
+        }
+    }
+}
+
+fn warn(path: &Path, res: io::Result<()>) {
+    if let Err(e) = res {
+        eprintln!("failed to write {}: {}", path.display(), e);
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opt = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.1?}", d),
+            None => "-".to_string(),
+        };
+
+        writeln!(f, "☆ Report: {}", self.policy)?;
+        writeln!(
+            f,
+            "{:>4} {:<24} {:>12} {:>12} {:>12} {:>12} {:>5}",
+            "id", "name", "turnaround", "waiting", "response", "cpu", "exit"
+        )?;
+
+        for p in &self.processes {
+            writeln!(
+                f,
+                "{:>4} {:<24} {:>12} {:>12} {:>12} {:>12} {:>5}",
+                p.id,
+                p.name,
+                opt(p.turnaround),
+                format!("{:.1?}", p.waiting),
+                opt(p.response),
+                format!("{:.1?}", p.cpu),
+                p.exit_code,
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{:>4} {:<24} {:>12} {:>12} {:>12}",
+            "",
+            "average",
+            format!("{:.1?}", self.avg_turnaround()),
+            format!("{:.1?}", self.avg_waiting()),
+            format!("{:.1?}", self.avg_response()),
+        )?;
+        writeln!(
+            f,
+            "☆ Makespan: {:.1?} ☆ Throughput: {:.2} processes/s",
+            self.makespan, self.throughput
+        )?;
+
+        if let Some(latency) = self.avg_msg_latency() {
+            let received: u32 = self.processes.iter().map(|p| p.received).sum();
+            writeln!(
+                f,
+                "☆ Message Latency: {:.1?} on average, over {} messages",
+                latency, received
+            )?;
+        }
+
+        if let Some((misses, jobs)) = self.deadline_misses() {
+            writeln!(f, "☆ Deadline Misses: {}/{}", misses, jobs)?;
+        }
+
+        if !self.blocked.is_empty() {
+            let name = |id| self.processes.iter().find(|p| p.id == id);
+            let blocked: Vec<_> = self
+                .blocked
+                .iter()
+                .map(|(id, on)| {
+                    let name = name(*id).map_or("", |p| p.name.as_str());
+                    format!("{} {} ({})", id, name, on)
+                })
+                .collect();
+            writeln!(f, "☆ Deadlocked or Blocked at End: {}", blocked.join(", "))?;
+        }
+
+        if !self.locks.is_empty() {
+            writeln!(
+                f,
+                "☆ Locks:\n{:<12} {:>9} {:>10} {:>12} {:>12} {:>12}",
+                "lock", "acquired", "contended", "waited", "held", "max held"
+            )?;
+        }
+        for lock in &self.locks {
+            let (name, held, max_held) = match lock.kind {
+                LockKind::Mutex => (
+                    "mutex",
+                    format!("{:.1?}", lock.stats.held),
+                    format!("{:.1?}", lock.stats.max_held),
+                ),
+                LockKind::Semaphore => ("semaphore", "-".to_string(), "-".to_string()),
+            };
+            writeln!(
+                f,
+                "{:<12} {:>9} {:>10} {:>12} {:>12} {:>12}",
+                format!("{} {}", name, lock.id),
+                lock.stats.acquired,
+                lock.stats.contended,
+                format!("{:.1?}", lock.stats.waited),
+                held,
+                max_held,
+            )?;
+        }
+
+        if self.utilization.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, "☆ CPU Utilization:")?;
+        for (id, u) in &self.utilization {
+            write!(f, " {}:{:.1}%", id, u * 100.0)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The averages of several reports side by side, one line per report.
+pub struct Comparison<'a>(pub &'a [Report]);
+
+impl fmt::Display for Comparison<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>12} {:>12} {:>12} {:>14} {:>10} {:>12} {:>10}",
+            "policy",
+            "turnaround",
+            "waiting",
+            "response",
+            "makespan",
+            "throughput/s",
+            "cpu",
+            "msg latency",
+            "misses"
+        )?;
+
+        for r in self.0 {
+            let cpu = match r.utilization.len() {
+                0 => "-".to_string(),
+                n => format!(
+                    "{:.1}%",
+                    r.utilization.iter().map(|(_, u)| u).sum::<f64>() / n as f64 * 100.0
+                ),
+            };
+
+            writeln!(
+                f,
+                "{:<10} {:>12} {:>12} {:>12} {:>12} {:>14.2} {:>10} {:>12} {:>10}",
+                r.policy,
+                format!("{:.1?}", r.avg_turnaround()),
+                format!("{:.1?}", r.avg_waiting()),
+                format!("{:.1?}", r.avg_response()),
+                format!("{:.1?}", r.makespan),
+                r.throughput,
+                cpu,
+                r.avg_msg_latency()
+                    .map_or_else(|| "-".to_string(), |d| format!("{:.1?}", d)),
+                r.deadline_misses()
+                    .map_or_else(|| "-".to_string(), |(m, j)| format!("{}/{}", m, j)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::simulate;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn process(id: u32, arrival: u64, finish: Option<u64>, waiting: u64) -> ProcessReport {
+        ProcessReport {
+            id,
+            name: format!("p{}", id),
+            arrival: ms(arrival),
+            finish: finish.map(ms),
+            turnaround: finish.map(|f| ms(f - arrival)),
+            waiting: ms(waiting),
+            response: Some(ms(waiting)),
+            cpu: ms(1),
+            exit_code: 0,
+            received: 0,
+            msg_latency: None,
+            jobs: 0,
+            misses: 0,
+            predict_error: None,
+        }
+    }
+
+    #[test]
+    fn averages_skip_unfinished_processes() {
+        let report = Report::from_processes(
+            "test".to_string(),
+            vec![
+                process(2, 10, Some(50), 4),
+                process(1, 0, Some(20), 2),
+                process(3, 10, None, 6),
+            ],
+            Vec::new(),
+        );
+
+        let ids: Vec<_> = report.processes.iter().map(|p| p.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(report.makespan, ms(50));
+        assert_eq!(report.throughput, 2.0 / 0.05);
+        assert_eq!(report.avg_turnaround(), ms(30));
+        assert_eq!(report.avg_waiting(), ms(4));
+    }
+
+    #[test]
+    fn utilization_counts_idle_time() {
+        let report = simulate(
+            "priority",
+            2,
+            r#"
+            [[process]]
+            name = "sleeper"
+            steps = [{ burst = "2ms" }, { sleep = "2ms" }, { burst = "2ms" }]
+            "#,
+        );
+
+        assert_eq!(report.makespan, ms(6));
+        assert_eq!(report.utilization.len(), 2);
+        let busy: f64 = report.utilization.iter().map(|(_, u)| u).sum();
+        assert!((busy - 4.0 / 6.0).abs() < 1e-9, "{}", busy);
+    }
+
+    #[test]
+    fn times_from_state_transitions() {
+        let report = simulate(
+            "priority",
+            1,
+            r#"
+            [[process]]
+            name = "first"
+            steps = [{ burst = "4ms" }, { exit = 0 }]
+
+            [[process]]
+            name = "sleeper"
+            arrival = "3ms"
+            steps = [{ burst = "2ms" }, { sleep = "2ms" }, { burst = "2ms" }, { exit = 0 }]
+            "#,
+        );
+        let p = report
+            .processes
+            .iter()
+            .find(|p| p.name == "sleeper")
+            .unwrap();
+
+        // ready at 3ms, runs at 4ms, asleep from 6ms to 8ms, done at 10ms
+        assert_eq!(p.arrival, ms(3));
+        assert_eq!(p.response, Some(ms(1)));
+        assert_eq!(p.finish, Some(ms(10)));
+        assert_eq!(p.turnaround, Some(ms(7)));
+        assert_eq!(p.waiting, ms(1));
+        assert_eq!(p.cpu, ms(4));
+    }
+
+    #[test]
+    fn deadline_misses() {
+        let report = simulate(
+            "edf",
+            1,
+            r#"
+            [[process]]
+            name = "fits"
+            period = "10ms"
+            wcet = "4ms"
+            loops = 5
+            steps = [{ burst = "4ms" }]
+
+            [[process]]
+            name = "overruns"
+            period = "20ms"
+            wcet = "5ms"
+            loops = 3
+            steps = [{ burst = "18ms" }]
+            "#,
+        );
+        let misses =
+            |r: &Report| -> Vec<_> { r.processes.iter().map(|p| (p.jobs, p.misses)).collect() };
+
+        // the 18ms bursts overrun every 20ms period, and push a job of "fits" late
+        assert_eq!(misses(&report), [(5, 1), (3, 3)]);
+        assert_eq!(report.deadline_misses(), Some((4, 8)));
+        assert!(report.to_string().contains("☆ Deadline Misses: 4/8"));
+        let comparison = Comparison(std::slice::from_ref(&report)).to_string();
+        assert!(comparison.lines().nth(1).unwrap().ends_with(" 4/8"));
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let path = std::env::temp_dir().join(format!("misses-{}.csv", std::process::id()));
+        fs::write(&path, csv).unwrap();
+        let read = Report::read_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(misses(&read), [(5, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn burst_prediction_error() {
+        let report = simulate(
+            "sjf",
+            1,
+            r#"
+            [[process]]
+            name = "predicted"
+            steps = [{ burst = "4ms" }, { read = "txt/浓度.txt" }, { burst = "12ms" }, { exit = 0 }]
+            "#,
+        );
+        let p = &report.processes[0];
+
+        // predicted 20ms then (4 + 20) / 2 = 12ms: off by 16ms, then exact
+        assert_eq!(p.predict_error, Some(ms(8)));
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let path = std::env::temp_dir().join(format!("predict-{}.csv", std::process::id()));
+        fs::write(&path, csv).unwrap();
+        let read = Report::read_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.processes[0].predict_error, Some(ms(8)));
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let report = Report::from_processes(
+            "test".to_string(),
+            vec![process(1, 0, Some(20), 2), process(2, 5, None, 3)],
+            Vec::new(),
+        );
+        let path = std::env::temp_dir().join(format!("report-{}.csv", std::process::id()));
+
+        report.write_csv(File::create(&path).unwrap()).unwrap();
+        let read = Report::read_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.processes.len(), 2);
+        assert_eq!(read.processes[0].turnaround, Some(ms(20)));
+        assert_eq!(read.processes[1].finish, None);
+        assert_eq!(read.makespan, report.makespan);
+    }
+}
@@ -0,0 +1,188 @@
+use std::ops::Range;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// The stream of each component, so they never share random numbers.
+pub const FACTORY: u64 = 1;
+pub const PERIODIC: u64 = 2;
+pub const POLICY: u64 = 0x100;
+pub const WORKER: u64 = 0x200;
+pub const FS: u64 = 0x300;
+
+/// A small seedable generator (SplitMix64). Every source of randomness owns
+/// one made from the run seed, so a run can be replayed with the same seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+pub trait SampleRange<T> {
+    fn sample(self, rng: &mut Rng) -> T;
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The generator of the component `stream` for the run `seed`.
+    pub fn stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self(seed ^ stream.wrapping_mul(0xA076_1D64_78BD_642F));
+        Self(rng.next_u64())
+    }
+
+    /// A seed for when none was given, print it to replay the run.
+    pub fn random_seed() -> u64 {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        Self(nanos).next_u64()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn gen<T, R: SampleRange<T>>(&mut self, range: R) -> T {
+        range.sample(self)
+    }
+
+    /// A lowercase word of `len` letters.
+    pub fn word(&mut self, len: RangeInclusive<usize>) -> String {
+        (0..self.gen(len))
+            .map(|_| (b'a' + self.gen(0..26_u32) as u8) as char)
+            .collect()
+    }
+
+    /// A few words, separated by `sep`.
+    pub fn words(&mut self, sep: &str) -> String {
+        let n = self.gen(1..=8_usize);
+
+        (0..n)
+            .map(|_| self.word(1..=10))
+            .collect::<Vec<_>>()
+            .join(sep)
+    }
+
+    /// A small JSON object of words and numbers.
+    pub fn json(&mut self) -> String {
+        let fields: Vec<_> = (0..self.gen(1..=5_usize))
+            .map(|_| {
+                let key = self.word(3..=8);
+                match self.gen(0..2_u32) {
+                    0 => format!("{:?}:{}", key, self.gen(0..10000_u32)),
+                    _ => format!("{:?}:{:?}", key, self.words(" ")),
+                }
+            })
+            .collect();
+
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+macro_rules! impl_sample_range {
+	($($t:ty => $u:ty),*) => {
+		$(
+			impl SampleRange<$t> for Range<$t> {
+				fn sample(self, rng: &mut Rng) -> $t {
+					assert!(self.start < self.end, "cannot sample empty range");
+					let span = self.end.wrapping_sub(self.start) as $u as u64;
+
+					self.start.wrapping_add((rng.next_u64() % span) as $t)
+				}
+			}
+
+			impl SampleRange<$t> for RangeInclusive<$t> {
+				fn sample(self, rng: &mut Rng) -> $t {
+					let (start, end) = self.into_inner();
+					assert!(start <= end, "cannot sample empty range");
+					let span = (end.wrapping_sub(start) as $u as u64).wrapping_add(1);
+
+					match span {
+						// the whole range of u64
+						0 => rng.next_u64() as $t,
+						_ => start.wrapping_add((rng.next_u64() % span) as $t),
+					}
+				}
+			}
+		)*
+	};
+}
+
+impl_sample_range!(i32 => u32, u32 => u32, u64 => u64, usize => usize);
+
+impl SampleRange<Duration> for Range<Duration> {
+    fn sample(self, rng: &mut Rng) -> Duration {
+        let nanos = (self.start.as_nanos() as u64..self.end.as_nanos() as u64).sample(rng);
+        Duration::from_nanos(nanos)
+    }
+}
+
+impl SampleRange<Duration> for RangeInclusive<Duration> {
+    fn sample(self, rng: &mut Rng) -> Duration {
+        let (start, end) = self.into_inner();
+        let nanos = (start.as_nanos() as u64..=end.as_nanos() as u64).sample(rng);
+        Duration::from_nanos(nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processes::RandomFactory;
+
+    fn take(mut rng: Rng, n: usize) -> Vec<u64> {
+        (0..n).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        assert_eq!(take(Rng::new(7), 8), take(Rng::new(7), 8));
+        assert_ne!(take(Rng::new(7), 8), take(Rng::new(8), 8));
+
+        assert_eq!(take(Rng::stream(7, FS), 8), take(Rng::stream(7, FS), 8));
+        assert_ne!(take(Rng::stream(7, FS), 8), take(Rng::stream(7, WORKER), 8));
+        assert_ne!(
+            take(Rng::stream(7, POLICY), 8),
+            take(Rng::stream(7, POLICY + 1), 8)
+        );
+    }
+
+    #[test]
+    fn ranges_in_bounds() {
+        let mut rng = Rng::new(1);
+
+        for _ in 0..1000 {
+            assert!((3..7).contains(&rng.gen(3..7_u32)));
+            assert!((-2..=2).contains(&rng.gen(-2..=2_i32)));
+            assert_eq!(rng.gen(5..=5_usize), 5);
+
+            let d = rng.gen(Duration::from_millis(1)..Duration::from_millis(2));
+            assert!((Duration::from_millis(1)..Duration::from_millis(2)).contains(&d));
+
+            let word = rng.word(1..=3);
+            assert!((1..=3).contains(&word.len()));
+            assert!(word.bytes().all(|b| b.is_ascii_lowercase()));
+        }
+        // the whole range of u64 does not overflow
+        rng.gen(0..=u64::MAX);
+    }
+
+    #[test]
+    fn same_seed_same_processes() {
+        let processes = |seed| {
+            let mut factory = RandomFactory::new(5, seed);
+            (&mut factory)
+                .map(|p| format!("{:?}", p))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(processes(3), processes(3));
+        assert_ne!(processes(3), processes(4));
+    }
+}
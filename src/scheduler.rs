@@ -1,23 +1,51 @@
+use crate::clock;
+use crate::event::Msg;
+use crate::event::MsgTx;
+use crate::event::SimEvent;
+use crate::family::Family;
 use crate::fs::fs_run;
 use crate::fs::init_txts;
-use crate::ops::Op;
+use crate::ipc::Mailboxes;
+use crate::kernel;
+use crate::kernel::Dispatch;
+use crate::locks::Locks;
 use crate::pcb::PInfo;
 use crate::pcb::Pcb;
 use crate::pcb::PollRes;
+use crate::pcb::ProcState;
 use crate::pcb::Process;
+use crate::pcb::RtTask;
 use crate::pcb::INIT_PRIORITY;
+use crate::policy::PriorityPolicy;
+use crate::policy::SchedulingPolicy;
 use crate::printer;
 use crate::printer::Printer;
+use crate::report::Exports;
+use crate::report::Report;
+use crate::rng;
+use crate::rng::Rng;
+use crate::sim::Simulation;
+use crate::timeline::SpanEnd;
+use crate::timeline::Timeline;
+use crate::timeline::Track;
+use crate::timer::Timer;
+use crate::util::convert::make_mut;
+use crate::util::convert::StaticRef;
+use crate::util::time_test;
 use crate::worker_info::WorkerInfo;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::Receiver;
 use crossbeam::sync::Parker;
 use crossbeam::sync::Unparker;
 use crossbeam_channel::Sender;
-use std::borrow::Cow;
-use std::collections::BinaryHeap;
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::collections::LinkedList;
+use std::hint;
+use std::panic;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::thread;
@@ -25,17 +53,15 @@ use std::thread::JoinHandle;
 use std::time::Duration;
 use std::time::Instant;
 use work_queue::Queue;
-use wutil::convert::make_mut;
-use wutil::convert::StaticRef;
-use wutil::convert::StaticRefArray;
-use wutil::random::gen;
-use wutil::types::SStr;
-use wutil::util::time_test;
 
 pub struct SchedulerBuilder {
     time_slice: Duration,
     print: bool,
     print_interval: Duration,
+    policy: Box<dyn SchedulingPolicy>,
+    seed: u64,
+    preempt: bool,
+    exports: Exports,
 }
 
 impl SchedulerBuilder {
@@ -44,6 +70,10 @@ impl SchedulerBuilder {
             time_slice: Duration::from_millis(20),
             print: true,
             print_interval: Duration::from_millis(200),
+            policy: Box::new(PriorityPolicy::new()),
+            seed: Rng::random_seed(),
+            preempt: false,
+            exports: Exports::default(),
         }
     }
 
@@ -62,6 +92,44 @@ impl SchedulerBuilder {
         self
     }
 
+    pub fn boxed_policy(mut self, policy: Box<dyn SchedulingPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Cut bursts longer than the time slice, instead of trusting `poll` to
+    /// return in time.
+    pub fn preempt(mut self, preempt: bool) -> Self {
+        self.preempt = preempt;
+        self
+    }
+
+    /// Every random choice of the scheduler derives from this seed.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Also write the final report, one row per process, as CSV to `path`.
+    pub fn report_csv(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exports.report_csv = Some(path.into());
+        self
+    }
+
+    /// Also write a Gantt chart of the run to `path`, as HTML when it ends
+    /// with `.html`, as SVG otherwise.
+    pub fn gantt(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exports.gantt = Some(path.into());
+        self
+    }
+
+    /// Also write a Chrome Trace Event file of the run to `path`.
+    pub fn trace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exports.trace = Some(path.into());
+        self
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn build(
         self,
         threads: usize,
@@ -69,29 +137,31 @@ impl SchedulerBuilder {
         printer: &'static Printer,
         share: &'static Share,
         worker_infos: &'static LinkedList<WorkerInfo>,
-        parkers: &'static StaticRefArray<(Parker, Unparker)>,
+        parkers: Vec<Parker>,
         msg_done: &'static AtomicBool,
+        // the unparkers of `parkers`, for the workers and the fs threads
         fs_unparkers: &'static Vec<Unparker>,
-        msg_tx: Sender<SStr>,
     ) -> Scheduler {
         // if print, start the print thread
         if self.print {
             // Safety: here in main thread single thread.
             let printer = unsafe { make_mut(printer) };
             printer.schedule_threads = threads;
+            printer.policy = self.policy.name();
+            printer.seed = self.seed;
             printer.worker_infos = Some(worker_infos);
             thread::spawn(move || printer::print(printer, self.print_interval));
         }
 
-        let mut workers = Vec::with_capacity(threads as usize);
+        let mut workers = Vec::with_capacity(threads);
         let mut local_queues = global_queue.local_queues();
         let (shutdown_tx, shutdown_rx) = unbounded();
-        let mut parkers_iter = parkers.iter();
+        let mut parkers_iter = parkers.into_iter();
         let mut unparkers_vec = Vec::with_capacity(threads);
 
         for id in 0..threads {
             let mut unparkers = Vec::with_capacity(threads - 1);
-            for (idx, (_, unparker)) in parkers.iter().enumerate() {
+            for (idx, unparker) in fs_unparkers.iter().enumerate() {
                 if id != idx {
                     unparkers.push(unparker.clone());
                 }
@@ -101,25 +171,36 @@ impl SchedulerBuilder {
 
         let (fs_tx, fs_rx) = unbounded();
 
-        for _ in 0..threads / 2 + 1 {
-            init_txts();
+        init_txts();
+
+        for id in 0..threads / 2 + 1 {
             let rx = fs_rx.clone();
-            thread::spawn(move || fs_run(rx, global_queue, fs_unparkers));
+            let rng = Rng::stream(self.seed, rng::FS + id as u64);
+            thread::spawn(move || fs_run(id, rx, global_queue, share, fs_unparkers, rng));
         }
 
         let mut unparkers_vec = unparkers_vec.into_iter();
+        let wakeups = Timer::new("wakeups", move |pcb: Pcb| {
+            pcb.set_state(ProcState::Ready);
+            global_queue.push(pcb);
+
+            for unparker in fs_unparkers {
+                unparker.unpark();
+            }
+        });
 
         for id in 0..threads {
-            let msg_tx = msg_tx.clone();
             let fs_tx = fs_tx.clone();
-            let (parker, unparker) = parkers_iter.next().unwrap();
+            let parker = parkers_iter.next().unwrap();
+            let unparker = parker.unparker().clone();
             let unparkers = unparkers_vec.next().unwrap();
 
             let mut local_queue = local_queues.next().unwrap();
             let shutdown_tx = shutdown_tx.clone();
+            let wakeups = wakeups.clone();
             let worker_info = WorkerInfo {
                 id,
-                start_point: Instant::now(),
+                start_point: clock::now(),
                 waiting_time: Duration::ZERO,
                 idle: false,
             };
@@ -129,9 +210,11 @@ impl SchedulerBuilder {
                 worker_infos.back().unwrap().static_ref_mut()
             };
 
-            let h = std::thread::spawn(move || {
-                let mut priority_queue = BinaryHeap::new();
+            let mut policy = self.policy.fork();
+            let mut rng = Rng::stream(self.seed, rng::WORKER + id as u64);
+            policy.seed(Rng::stream(self.seed, rng::POLICY + id as u64).next_u64());
 
+            let h = std::thread::spawn(move || {
                 loop {
                     let processes = share.processes.load(Ordering::Relaxed);
                     let done = share.done.load(Ordering::Relaxed);
@@ -140,7 +223,7 @@ impl SchedulerBuilder {
                     let mut cnt = 0;
 
                     while let Some(pcb) = local_queue.pop() {
-                        priority_queue.push(pcb);
+                        policy.on_enqueue(pcb);
                         cnt += 1;
 
                         if cnt >= avg_processes {
@@ -148,59 +231,92 @@ impl SchedulerBuilder {
                         }
                     }
 
-                    if priority_queue.is_empty() {
+                    if policy.is_empty() {
                         worker_info_ref.idle = true;
                         let (time, ()) = time_test(|| parker.park());
 
+                        // idle until the end too, for the utilization
+                        worker_info_ref.waiting_time += time;
                         if unsafe { WORKER_RETURN } {
                             return;
                         }
-
-                        worker_info_ref.waiting_time += time;
                         worker_info_ref.idle = false;
                     } else {
-                        while let Some(mut pcb) = priority_queue.pop() {
-                            match pcb.poll_wrap(self.time_slice) {
+                        // the process which keeps the worker when the policy does not preempt it
+                        let mut running = None;
+
+                        while let Some(mut pcb) = running.take().or_else(|| policy.pick_next()) {
+                            let start = clock::now();
+                            let quantum = policy.quantum(pcb.pinfo, self.time_slice);
+                            let (used, poll_res) = pcb.poll_wrap(id, quantum, self.preempt);
+                            let record = |pid, reason| {
+                                share.timeline.record(
+                                    Track::Worker(id),
+                                    pid,
+                                    start,
+                                    start + used,
+                                    reason,
+                                )
+                            };
+                            // a process blocked, which may have been the last one able to run
+                            let blocked = |woken: Option<Pcb>| match woken {
+                                Some(pcb) => wakeups.add(clock::now(), pcb),
+                                None if share.stalled() => {
+                                    let _ = shutdown_tx.send(());
+                                }
+                                None => {}
+                            };
+
+                            match poll_res {
                                 PollRes::Polling(op) => {
-                                    match op {
-                                        Op::None => {}
-                                        Op::FileOp(file_op) => {
-                                            fs_tx.send((pcb, file_op));
+                                    policy.on_tick(&pcb, used);
+                                    let (pcb, yielded) = match kernel::dispatch(pcb, op, id, share)
+                                    {
+                                        Dispatch::Ready(pcb) => (pcb, false),
+                                        Dispatch::Yield(pcb) => (pcb, true),
+                                        Dispatch::ReadyWith(pcb, other) => {
+                                            wakeups.add(clock::now(), other);
+                                            (pcb, false)
+                                        }
+                                        Dispatch::Io(pcb, file_op) => {
+                                            record(pcb.pinfo.id, SpanEnd::Blocked);
+                                            policy.on_block(&pcb);
+                                            let _ = fs_tx.send((pcb, file_op));
                                             continue;
                                         }
-                                        Op::AddPriority(p) => {
-                                            unsafe { pcb.pinfo.static_ref_mut() }
-                                                .metric
-                                                .priority += p;
-
-                                            msg_tx.send(Cow::Owned(format!(
-                                                "{} ADD PRIORITY => {}",
-                                                pcb.p.name(),
-                                                p
-                                            )));
+                                        Dispatch::Sleep(pcb, until) => {
+                                            record(pcb.pinfo.id, SpanEnd::Sleep);
+                                            policy.on_block(&pcb);
+                                            wakeups.add(until, pcb);
+                                            continue;
                                         }
-                                        Op::SubPriority(p) => {
-                                            unsafe { pcb.pinfo.static_ref_mut() }
-                                                .metric
-                                                .priority -= p;
-                                            msg_tx.send(Cow::Owned(format!(
-                                                "{} SUB PRIORITY => {}",
-                                                pcb.p.name(),
-                                                p
-                                            )));
+                                        Dispatch::Wait(pcb, child) => {
+                                            record(pcb.pinfo.id, SpanEnd::Blocked);
+                                            policy.on_block(&pcb);
+                                            blocked(kernel::wait(pcb, child, share));
+                                            continue;
                                         }
-                                        Op::SetPriority(p) => {
-                                            unsafe { pcb.pinfo.static_ref_mut() }.metric.priority =
-                                                p;
-                                            msg_tx.send(Cow::Owned(format!(
-                                                "{} SET PRIORITY => {}",
-                                                pcb.p.name(),
-                                                p
-                                            )));
+                                        Dispatch::Recv(pcb) => {
+                                            record(pcb.pinfo.id, SpanEnd::Blocked);
+                                            policy.on_block(&pcb);
+                                            blocked(kernel::recv(pcb, share));
+                                            continue;
                                         }
+                                        Dispatch::Block(pcb, on) => {
+                                            record(pcb.pinfo.id, SpanEnd::Blocked);
+                                            policy.on_block(&pcb);
+                                            blocked(kernel::block(pcb, on, share));
+                                            continue;
+                                        }
+                                    };
+                                    record(pcb.pinfo.id, SpanEnd::of(&pcb));
+
+                                    if !yielded && !policy.preempt(&pcb) {
+                                        running = Some(pcb);
+                                        continue;
                                     }
 
-                                    let choice = gen(0..threads + 1);
+                                    let choice = rng.gen(0..threads + 1);
 
                                     if choice == 0 {
                                         local_queue.global().push(pcb);
@@ -208,18 +324,24 @@ impl SchedulerBuilder {
                                         local_queue.push(pcb);
                                     }
 
-                                    let maybe_awaken = gen(0..unparkers.len());
-                                    unsafe {
-                                        unparkers.get_unchecked(maybe_awaken).unpark();
+                                    // a single worker has no one else to wake
+                                    if !unparkers.is_empty() {
+                                        let maybe_awaken = rng.gen(0..unparkers.len());
+                                        unsafe {
+                                            unparkers.get_unchecked(maybe_awaken).unpark();
+                                        }
                                     }
                                 }
                                 PollRes::Ready => {
-                                    pcb.done();
-
-                                    let done = share.done.fetch_add(1, Ordering::Relaxed);
+                                    record(pcb.pinfo.id, SpanEnd::Exit);
+                                    policy.on_exit(&pcb);
+                                    if let Some(parent) = kernel::exit(pcb, id, share) {
+                                        wakeups.add(clock::now(), parent);
+                                    }
 
-                                    if done + 1 == processes {
-                                        shutdown_tx.send(processes);
+                                    share.done.fetch_add(1, Ordering::Relaxed);
+                                    if share.stalled() {
+                                        let _ = shutdown_tx.send(());
                                     }
                                 }
                             }
@@ -230,41 +352,100 @@ impl SchedulerBuilder {
 
             workers.push(Worker {
                 handle: h,
-                unparker: unparker.clone(),
+                unparker,
             });
         }
 
         drop(fs_tx);
-        // drop(msg_tx);
 
         Scheduler {
+            wakeups,
+            table: ProcessTable::new(threads, printer, share),
+            policy: self.policy,
             workers,
-            printer,
             print: self.print,
-            next_id: 1,
-            share,
             ready_queue: global_queue,
             shutdown: shutdown_rx,
             msg_done,
+            share,
+            worker_infos,
+            exports: self.exports,
         }
     }
+
+    /// Run in virtual time on `cpus` simulated CPUs instead of threads.
+    pub fn simulate(
+        self,
+        cpus: usize,
+        printer: &'static Printer,
+        share: &'static Share,
+        worker_infos: &'static LinkedList<WorkerInfo>,
+        msg_done: &'static AtomicBool,
+    ) -> Simulation {
+        let mut policy = self.policy;
+
+        clock::set_virtual(true);
+        init_txts();
+        policy.seed(Rng::stream(self.seed, rng::POLICY).next_u64());
+
+        if self.print {
+            // Safety: here in main thread single thread.
+            let printer = unsafe { make_mut(printer) };
+            printer.schedule_threads = cpus;
+            printer.policy = policy.name();
+            printer.seed = self.seed;
+            printer.worker_infos = Some(worker_infos);
+        }
+
+        Simulation::new(
+            cpus,
+            self.time_slice,
+            self.preempt,
+            self.print,
+            policy,
+            ProcessTable::new(cpus, printer, share),
+            worker_infos,
+            msg_done,
+            self.exports,
+        )
+    }
 }
 
 static mut WORKER_RETURN: bool = false;
 
 pub struct Scheduler {
+    table: ProcessTable,
+    policy: Box<dyn SchedulingPolicy>,
     ready_queue: &'static Queue<Pcb>,
+    /// The processes executed ahead of their arrival, and the sleeping ones.
+    wakeups: Timer<Pcb>,
     share: &'static Share,
     workers: Vec<Worker>,
-    printer: &'static Printer,
     print: bool,
-    next_id: u32,
-    shutdown: Receiver<usize>,
+    shutdown: Receiver<()>,
     msg_done: &'static AtomicBool,
+    worker_infos: &'static LinkedList<WorkerInfo>,
+    exports: Exports,
+}
+
+/// Registers new processes with `Share`, and runs the admission test of
+/// real-time processes.
+pub struct ProcessTable {
+    threads: usize,
+    /// The utilization of each admitted real-time process.
+    utilizations: Vec<f64>,
+    printer: &'static Printer,
+    share: &'static Share,
 }
 
 /// The share data between schedule threads and main thread of `Scheduler`.
 pub struct Share {
+    pub pinfos: Mutex<HashMap<u32, &'static PInfo>>,
+    pub next_id: AtomicU32,
+    pub family: Mutex<Family>,
+    pub mailboxes: Mutex<Mailboxes>,
+    pub locks: Mutex<Locks>,
+    pub timeline: Timeline,
     pub processes: AtomicUsize,
     pub done: AtomicUsize,
     pub printer_done: AtomicBool,
@@ -279,69 +460,289 @@ struct Worker {
 impl Share {
     pub fn new() -> Self {
         Self {
+            pinfos: Mutex::new(HashMap::new()),
+            next_id: AtomicU32::new(1),
+            family: Mutex::new(Family::default()),
+            mailboxes: Mutex::new(Mailboxes::default()),
+            locks: Mutex::new(Locks::default()),
+            timeline: Timeline::new(),
             processes: AtomicUsize::new(0),
             done: AtomicUsize::new(0),
             printer_done: AtomicBool::new(false),
             scheduler_done: AtomicBool::new(false),
         }
     }
+
+    pub fn pinfo(&self, id: u32) -> Option<&'static PInfo> {
+        self.pinfos.lock().get(&id).copied()
+    }
+
+    /// Whether no process can run anymore: each one is done, or blocked on
+    /// a mutex, semaphore, condvar, message or child, which only another one
+    /// could give it.
+    pub fn stalled(&self) -> bool {
+        // done first: a process only leaves a blocked one by waking it
+        let done = self.done.load(Ordering::Relaxed);
+        let blocked = {
+            let family = self.family.lock();
+            let mailboxes = self.mailboxes.lock();
+            let locks = self.locks.lock();
+
+            family.blocked() + mailboxes.blocked() + locks.blocked()
+        };
+
+        done + blocked == self.processes.load(Ordering::Relaxed)
+    }
+
+    /// Drop the processes blocked for good at the end of a run, and with
+    /// them their msg senders. Returns their ids, with what they block on.
+    pub fn sweep(&self) -> Vec<(u32, String)> {
+        // `Mailboxes::hang_up` woke the receivers already
+        let mut blocked = self.family.lock().drain();
+
+        blocked.append(&mut self.locks.lock().drain());
+
+        let mut blocked: Vec<_> = blocked
+            .into_iter()
+            .map(|(pcb, on)| (pcb.pinfo.id, on))
+            .collect();
+        blocked.sort();
+        blocked
+    }
+
+    /// Every process so far, by id.
+    pub fn sorted_pinfos(&self) -> Vec<&'static PInfo> {
+        let mut pinfos: Vec<_> = self.pinfos.lock().values().copied().collect();
+
+        pinfos.sort_by_key(|p| p.id);
+        pinfos
+    }
+
+    fn register(&self, pcb: &Pcb) {
+        self.pinfos.lock().insert(pcb.pinfo.id, pcb.pinfo);
+        self.processes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Create a child of `parent`, with its priority, to enqueue right away.
+    pub fn spawn(&self, parent: &Pcb, p: &'static mut dyn Process) -> Pcb {
+        let pinfo = Box::leak(Box::new(PInfo::new()));
+        pinfo.metric.priority = parent.pinfo.metric.priority;
+        pinfo.parent = Some(parent.pinfo.id);
+        pinfo.create();
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let pcb = Pcb::new(id, p, parent.msg_tx.clone(), pinfo);
+
+        self.family.lock().add_child(parent.pinfo.id, id);
+        self.register(&pcb);
+        pcb
+    }
 }
 
-// 1. Self starts
-// 2. Ready for new process && running process which can run
-impl Scheduler {
-    pub fn execute(
+/// When a process enters the ready queue.
+#[derive(Debug, Clone, Copy)]
+pub enum Arrival {
+    At(Instant),
+    /// That long after it is executed.
+    After(Duration),
+}
+
+pub trait Executor {
+    /// Admit the process now, it stays `ProcState::New` until `arrival`.
+    fn execute_at(
+        &mut self,
+        arrival: Arrival,
+        p: &'static mut dyn Process,
+        msg_tx: Sender<Msg>,
+        pinfo: &'static PInfo,
+        priority: i32,
+    );
+
+    fn execute_priority(
+        &mut self,
+        p: &'static mut dyn Process,
+        msg_tx: Sender<Msg>,
+        pinfo: &'static PInfo,
+        priority: i32,
+    ) {
+        self.execute_at(Arrival::After(Duration::ZERO), p, msg_tx, pinfo, priority);
+    }
+
+    fn execute(
         &mut self,
         p: &'static mut (dyn Process + Send + Sync),
-        msg_rx: Sender<SStr>,
+        msg_tx: Sender<Msg>,
         pinfo: &'static PInfo,
     ) {
-        self.execute_priority(p, msg_rx, pinfo, INIT_PRIORITY);
+        self.execute_priority(p, msg_tx, pinfo, INIT_PRIORITY);
     }
+}
 
-    pub fn execute_priority(
+impl Arrival {
+    /// The `clock::now()` of the arrival. An instant is as far from now in
+    /// virtual time as it is in real time.
+    pub fn time(self) -> Duration {
+        match self {
+            Arrival::At(at) => clock::now() + at.saturating_duration_since(Instant::now()),
+            Arrival::After(d) => clock::now() + d,
+        }
+    }
+}
+
+impl From<Instant> for Arrival {
+    fn from(at: Instant) -> Self {
+        Arrival::At(at)
+    }
+}
+
+impl From<Duration> for Arrival {
+    fn from(d: Duration) -> Self {
+        Arrival::After(d)
+    }
+}
+
+impl ProcessTable {
+    pub fn new(threads: usize, printer: &'static Printer, share: &'static Share) -> Self {
+        Self {
+            threads,
+            utilizations: Vec::new(),
+            printer,
+            share,
+        }
+    }
+
+    pub fn share(&self) -> &'static Share {
+        self.share
+    }
+
+    pub fn printer(&self) -> &'static Printer {
+        self.printer
+    }
+
+    pub fn report<'a>(
+        &self,
+        policy: &str,
+        blocked: Vec<(u32, String)>,
+        workers: impl IntoIterator<Item = &'a WorkerInfo>,
+    ) -> Report {
+        Report::new(
+            policy,
+            self.share.sorted_pinfos(),
+            self.share.locks.lock().reports(),
+            blocked,
+            workers,
+        )
+    }
+
+    pub fn admit(
         &mut self,
+        policy: &dyn SchedulingPolicy,
         p: &'static mut dyn Process,
-        msg_tx: Sender<SStr>,
+        msg_tx: Sender<Msg>,
         pinfo: &'static PInfo,
         priority: i32,
-    ) {
+    ) -> Pcb {
+        let id = self.share.next_id.fetch_add(1, Ordering::Relaxed);
         // Safety: here in main thread single thread.
         let pinfo = unsafe { make_mut(pinfo) };
         pinfo.metric.priority = priority;
+        pinfo.rt = RtTask::new(p);
 
-        self.ready_queue
-            .push(Pcb::new(self.next_id, p, msg_tx, pinfo));
+        if let Some(rt) = &mut pinfo.rt {
+            self.utilizations.push(rt.utilization());
 
-        unsafe { make_mut(self.printer) }.pinfos.push_back(pinfo);
-        self.share.processes.fetch_add(1, Ordering::Relaxed);
-        self.notify_all();
-        self.next_id += 1;
+            if !policy.admit(&self.utilizations, self.threads) {
+                let utilization = self.utilizations.iter().sum();
+
+                self.utilizations.pop();
+                rt.admitted = false;
+                MsgTx::new(msg_tx.clone(), id, None)
+                    .send(p.name(), SimEvent::Rejected { utilization });
+            }
+        }
+
+        pinfo.create();
+
+        let pcb = Pcb::new(id, p, msg_tx, pinfo);
+
+        self.share.register(&pcb);
+        pcb
     }
+}
 
-    pub fn infinite_run(self) {
-        for w in self.workers {
-            w.handle.join().unwrap();
+// 1. Self starts
+// 2. Ready for new process && running process which can run
+impl Executor for Scheduler {
+    fn execute_at(
+        &mut self,
+        arrival: Arrival,
+        p: &'static mut dyn Process,
+        msg_tx: Sender<Msg>,
+        pinfo: &'static PInfo,
+        priority: i32,
+    ) {
+        let at = arrival.time();
+        let pcb = self.table.admit(&*self.policy, p, msg_tx, pinfo, priority);
+
+        if at > clock::now() {
+            self.wakeups.add(at, pcb);
+            return;
         }
+
+        pcb.set_state(ProcState::Ready);
+        self.ready_queue.push(pcb);
+        self.notify_all();
     }
+}
 
-    pub fn join(self) {
-        while let Ok(processes) = self.shutdown.recv() {
-            if processes == self.share.processes.load(Ordering::Relaxed) {
+impl Scheduler {
+    pub fn join(self) -> Report {
+        // a worker may signal before every process was executed
+        while self.shutdown.recv().is_ok() {
+            if !self.share.stalled() {
+                continue;
+            }
+            let woken = self.share.mailboxes.lock().hang_up();
+
+            if woken.is_empty() {
                 break;
             }
+            for pcb in woken {
+                self.wakeups.add(clock::now(), pcb);
+            }
         }
 
         unsafe {
             WORKER_RETURN = true;
         }
         self.notify_all();
+        for w in self.workers {
+            // a crashed worker crashes the run, rather than end it with a bogus report
+            if let Err(panic) = w.handle.join() {
+                panic::resume_unwind(panic);
+            }
+        }
 
         self.share.scheduler_done.store(true, Ordering::Relaxed);
 
+        let blocked = self.share.sweep();
+
         /* wait for printer && another msg console working done! */
-        while !self.share.printer_done.load(Ordering::Relaxed) {}
-        while !self.msg_done.load(Ordering::Relaxed) {}
+        while self.print && !self.share.printer_done.load(Ordering::Relaxed) {
+            hint::spin_loop();
+        }
+        while !self.msg_done.load(Ordering::Relaxed) {
+            hint::spin_loop();
+        }
+
+        let report = self
+            .table
+            .report(self.policy.name(), blocked, self.worker_infos);
+        if self.print {
+            println!("{}", report);
+        }
+        self.exports.write(&report, self.share);
+        report
     }
 
     fn notify_all(&self) {
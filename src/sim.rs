@@ -0,0 +1,577 @@
+use crate::clock;
+use crate::event::Msg;
+use crate::fs::file_op;
+use crate::fs::FileOp;
+use crate::kernel;
+use crate::kernel::Dispatch;
+use crate::pcb::PInfo;
+use crate::pcb::Pcb;
+use crate::pcb::PollRes;
+use crate::pcb::ProcState;
+use crate::pcb::Process;
+use crate::policy::SchedulingPolicy;
+use crate::report::Exports;
+use crate::report::Report;
+use crate::scheduler::Arrival;
+use crate::scheduler::Executor;
+use crate::scheduler::ProcessTable;
+use crate::timeline::SpanEnd;
+use crate::timeline::Track;
+use crate::util::convert::make_mut;
+use crate::util::convert::StaticRef;
+use crate::worker_info::WorkerInfo;
+use crossbeam_channel::Sender;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::LinkedList;
+use std::mem;
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// How long a file op takes in virtual time.
+const IO_LATENCY: Duration = Duration::from_millis(5);
+
+enum Event {
+    /// `cpu` is done with the poll of `pcb`, which took `used`.
+    PollDone {
+        cpu: usize,
+        pcb: Pcb,
+        used: Duration,
+        poll_res: PollRes,
+    },
+    IoDone(Pcb),
+    /// `pcb` enters the ready queue.
+    Arrive(Pcb),
+    /// `pcb` is done sleeping.
+    Wake(Pcb),
+}
+
+struct Timed {
+    at: Duration,
+    // the order of scheduling breaks ties, so a run is reproducible
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Timed {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Timed {}
+
+impl PartialOrd for Timed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timed {
+    // reversed: the earliest event on the top of the heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+/// A discrete-event simulation of the scheduler.
+///
+/// One thread plays all the CPUs: a poll takes no wall time, the virtual
+/// clock jumps to the end of whatever `clock::burn` the process asked for.
+pub struct Simulation {
+    time_slice: Duration,
+    preempt: bool,
+    print: bool,
+    policy: Box<dyn SchedulingPolicy>,
+    table: ProcessTable,
+    /// When each CPU went idle, `None` while it is running a process.
+    idle_since: Vec<Option<Duration>>,
+    /// When each file system lane is done with its file op: one per file op
+    /// under way, so they do not overlap on the timeline.
+    io_lanes: Vec<Duration>,
+    worker_infos: Vec<&'static mut WorkerInfo>,
+    events: BinaryHeap<Timed>,
+    seq: u64,
+    msg_done: &'static AtomicBool,
+    exports: Exports,
+}
+
+impl Simulation {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        cpus: usize,
+        time_slice: Duration,
+        preempt: bool,
+        print: bool,
+        policy: Box<dyn SchedulingPolicy>,
+        table: ProcessTable,
+        worker_infos: &'static LinkedList<WorkerInfo>,
+        msg_done: &'static AtomicBool,
+        exports: Exports,
+    ) -> Self {
+        let now = clock::now();
+        let worker_infos = (0..cpus)
+            .map(|id| unsafe {
+                make_mut(worker_infos).push_back(WorkerInfo {
+                    id,
+                    start_point: now,
+                    waiting_time: Duration::ZERO,
+                    idle: true,
+                });
+                worker_infos.back().unwrap().static_ref_mut()
+            })
+            .collect();
+
+        Self {
+            time_slice,
+            preempt,
+            print,
+            policy,
+            table,
+            idle_since: vec![Some(now); cpus],
+            io_lanes: Vec::new(),
+            worker_infos,
+            events: BinaryHeap::new(),
+            seq: 0,
+            msg_done,
+            exports,
+        }
+    }
+
+    fn schedule(&mut self, at: Duration, event: Event) {
+        self.events.push(Timed {
+            at,
+            seq: self.seq,
+            event,
+        });
+        self.seq += 1;
+    }
+
+    fn run_on(&mut self, cpu: usize, mut pcb: Pcb) {
+        let now = clock::now();
+
+        if let Some(since) = self.idle_since[cpu].take() {
+            self.worker_infos[cpu].waiting_time += now - since;
+            self.worker_infos[cpu].idle = false;
+        }
+
+        let quantum = self.policy.quantum(pcb.pinfo, self.time_slice);
+        let (used, poll_res) = pcb.poll_wrap(cpu, quantum, self.preempt);
+
+        self.schedule(
+            now + used,
+            Event::PollDone {
+                cpu,
+                pcb,
+                used,
+                poll_res,
+            },
+        );
+    }
+
+    fn idle(&mut self, cpu: usize) {
+        self.idle_since[cpu] = Some(clock::now());
+        self.worker_infos[cpu].idle = true;
+    }
+
+    fn dispatch_idle(&mut self) {
+        for cpu in 0..self.idle_since.len() {
+            if self.idle_since[cpu].is_none() {
+                continue;
+            }
+
+            match self.policy.pick_next() {
+                Some(pcb) => self.run_on(cpu, pcb),
+                None => return,
+            }
+        }
+    }
+
+    /// Record the poll of `pcb` on `cpu`, which ends now.
+    fn record(&self, cpu: usize, pcb: &Pcb, used: Duration, reason: SpanEnd) {
+        let now = clock::now();
+        self.table.share().timeline.record(
+            Track::Worker(cpu),
+            pcb.pinfo.id,
+            now - used,
+            now,
+            reason,
+        );
+    }
+
+    fn block_io(&mut self, mut pcb: Pcb, op: FileOp) {
+        let now = clock::now();
+
+        let lane = match self.io_lanes.iter().position(|&end| end <= now) {
+            Some(lane) => lane,
+            None => {
+                self.io_lanes.push(now);
+                self.io_lanes.len() - 1
+            }
+        };
+        self.io_lanes[lane] = now + IO_LATENCY;
+
+        self.policy.on_block(&pcb);
+        self.table.share().timeline.record(
+            Track::Fs(lane),
+            pcb.pinfo.id,
+            now,
+            now + IO_LATENCY,
+            op.span_end(),
+        );
+        file_op(&mut pcb, op);
+        self.schedule(now + IO_LATENCY, Event::IoDone(pcb));
+    }
+
+    /// Run until every process is done.
+    pub fn run(mut self) -> Report {
+        loop {
+            self.dispatch_idle();
+
+            let Some(Timed { at, event, .. }) = self.events.pop() else {
+                // every process is done or blocked: no message can come anymore
+                let woken = self.table.share().mailboxes.lock().hang_up();
+
+                if woken.is_empty() {
+                    break;
+                }
+                for pcb in woken {
+                    self.schedule(clock::now(), Event::Wake(pcb));
+                }
+                continue;
+            };
+            clock::advance_to(at);
+            let share = self.table.share();
+
+            match event {
+                Event::PollDone {
+                    cpu,
+                    pcb,
+                    used,
+                    poll_res,
+                } => match poll_res {
+                    PollRes::Polling(op) => {
+                        self.policy.on_tick(&pcb, used);
+
+                        let (pcb, yielded) = match kernel::dispatch(pcb, op, cpu, share) {
+                            Dispatch::Ready(pcb) => (pcb, false),
+                            Dispatch::Yield(pcb) => (pcb, true),
+                            Dispatch::ReadyWith(pcb, other) => {
+                                self.schedule(at, Event::Wake(other));
+                                (pcb, false)
+                            }
+                            Dispatch::Io(pcb, op) => {
+                                self.record(cpu, &pcb, used, SpanEnd::Blocked);
+                                self.block_io(pcb, op);
+                                self.idle(cpu);
+                                continue;
+                            }
+                            Dispatch::Sleep(pcb, until) => {
+                                self.record(cpu, &pcb, used, SpanEnd::Sleep);
+                                self.policy.on_block(&pcb);
+                                self.schedule(until, Event::Wake(pcb));
+                                self.idle(cpu);
+                                continue;
+                            }
+                            Dispatch::Wait(pcb, child) => {
+                                self.record(cpu, &pcb, used, SpanEnd::Blocked);
+                                self.policy.on_block(&pcb);
+                                if let Some(pcb) = kernel::wait(pcb, child, share) {
+                                    self.schedule(at, Event::Wake(pcb));
+                                }
+                                self.idle(cpu);
+                                continue;
+                            }
+                            Dispatch::Recv(pcb) => {
+                                self.record(cpu, &pcb, used, SpanEnd::Blocked);
+                                self.policy.on_block(&pcb);
+                                if let Some(pcb) = kernel::recv(pcb, share) {
+                                    self.schedule(at, Event::Wake(pcb));
+                                }
+                                self.idle(cpu);
+                                continue;
+                            }
+                            Dispatch::Block(pcb, on) => {
+                                self.record(cpu, &pcb, used, SpanEnd::Blocked);
+                                self.policy.on_block(&pcb);
+                                if let Some(pcb) = kernel::block(pcb, on, share) {
+                                    self.schedule(at, Event::Wake(pcb));
+                                }
+                                self.idle(cpu);
+                                continue;
+                            }
+                        };
+                        self.record(cpu, &pcb, used, SpanEnd::of(&pcb));
+
+                        if !yielded && !self.policy.preempt(&pcb) {
+                            self.run_on(cpu, pcb);
+                            continue;
+                        }
+                        self.policy.on_enqueue(pcb);
+                        self.idle(cpu);
+                    }
+                    PollRes::Ready => {
+                        self.record(cpu, &pcb, used, SpanEnd::Exit);
+                        self.policy.on_exit(&pcb);
+                        if let Some(parent) = kernel::exit(pcb, cpu, share) {
+                            self.schedule(at, Event::Wake(parent));
+                        }
+                        share.done.fetch_add(1, atomic::Ordering::Relaxed);
+                        self.idle(cpu);
+                    }
+                },
+                Event::IoDone(pcb) | Event::Arrive(pcb) | Event::Wake(pcb) => {
+                    pcb.set_state(ProcState::Ready);
+                    self.policy.on_enqueue(pcb);
+                }
+            }
+        }
+
+        let now = clock::now();
+        for (cpu, since) in self.idle_since.iter().enumerate() {
+            if let Some(since) = since {
+                self.worker_infos[cpu].waiting_time += now - *since;
+            }
+        }
+
+        let share = self.table.share();
+        share.scheduler_done.store(true, atomic::Ordering::Relaxed);
+
+        if self.print {
+            println!("{}", self.table.printer());
+        }
+
+        // no event is left to wake the blocked processes
+        let blocked = share.sweep();
+        let report = self.table.report(
+            self.policy.name(),
+            blocked,
+            self.worker_infos.iter().map(|w| &**w),
+        );
+        let exports = mem::take(&mut self.exports);
+        let print = self.print;
+
+        // every Pcb, and so every msg sender, is dropped: wait for the rest
+        // of the messages to go out
+        let msg_done = self.msg_done;
+        drop(self);
+        while !msg_done.load(atomic::Ordering::Relaxed) {
+            std::hint::spin_loop();
+        }
+
+        if print {
+            println!("{}", report);
+        }
+        exports.write(&report, share);
+        report
+    }
+}
+
+impl Executor for Simulation {
+    fn execute_at(
+        &mut self,
+        arrival: Arrival,
+        p: &'static mut dyn Process,
+        msg_tx: Sender<Msg>,
+        pinfo: &'static PInfo,
+        priority: i32,
+    ) {
+        let at = arrival.time();
+        let pcb = self.table.admit(&*self.policy, p, msg_tx, pinfo, priority);
+
+        if at > clock::now() {
+            self.schedule(at, Event::Arrive(pcb));
+            return;
+        }
+
+        pcb.set_state(ProcState::Ready);
+        self.policy.on_enqueue(pcb);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::locks::LockProtocol;
+    use crate::policy;
+    use crate::printer::Printer;
+    use crate::scheduler::SchedulerBuilder;
+    use crate::scheduler::Share;
+    use crate::workload::Workload;
+    use crossbeam_channel::unbounded;
+    use parking_lot::Mutex;
+    use std::thread;
+
+    /// The simulations share the virtual clock: one at a time.
+    static CLOCK: Mutex<()> = Mutex::new(());
+
+    /// Run the TOML `workload` to the end in virtual time, on `cpus` CPUs.
+    pub fn simulate(policy: &str, cpus: usize, workload: &str) -> Report {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name(policy).unwrap());
+
+        simulate_with(builder, cpus, LockProtocol::None, workload)
+    }
+
+    pub fn simulate_with(
+        builder: SchedulerBuilder,
+        cpus: usize,
+        protocol: LockProtocol,
+        workload: &str,
+    ) -> Report {
+        simulate_share(builder, cpus, protocol, workload).0
+    }
+
+    /// As `simulate_with`, with what the run left in the `Share`, as its
+    /// timeline.
+    pub fn simulate_share(
+        builder: SchedulerBuilder,
+        cpus: usize,
+        protocol: LockProtocol,
+        workload: &str,
+    ) -> (Report, &'static Share) {
+        let workload = toml::from_str(workload).unwrap();
+
+        run(builder, cpus, protocol, workload, Vec::new)
+    }
+
+    /// Run the processes `make` returns to the end in virtual time, on `cpus`
+    /// CPUs, and return how each one did. `make` runs once the clock is reset.
+    pub fn simulate_processes(
+        policy: &str,
+        cpus: usize,
+        make: impl FnOnce() -> Vec<Box<dyn Process>>,
+    ) -> Vec<&'static PInfo> {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name(policy).unwrap());
+        let workload = toml::from_str("").unwrap();
+        let (_, share) = run(builder, cpus, LockProtocol::None, workload, make);
+
+        share.sorted_pinfos()
+    }
+
+    fn run(
+        builder: SchedulerBuilder,
+        cpus: usize,
+        protocol: LockProtocol,
+        workload: Workload,
+        make: impl FnOnce() -> Vec<Box<dyn Process>>,
+    ) -> (Report, &'static Share) {
+        let _clock = CLOCK.lock();
+        crate::static_refs! {
+            share = Share::new();
+            printer = Printer::new(share);
+            worker_infos = LinkedList::new();
+            msg_done = AtomicBool::new(false);
+        };
+
+        let mut locks = share.locks.lock();
+        locks.set_protocol(protocol);
+        for mutex in &workload.mutexes {
+            locks.set_ceiling(mutex.id, mutex.ceiling);
+        }
+        for sem in &workload.semaphores {
+            locks.init_sem(sem.id, sem.count);
+        }
+        drop(locks);
+
+        let (msg_tx, msg_rx) = unbounded();
+        thread::spawn(move || {
+            for _ in msg_rx {}
+            msg_done.store(true, atomic::Ordering::Relaxed);
+        });
+
+        let mut sim =
+            builder
+                .print(false)
+                .seed(1)
+                .simulate(cpus, printer, share, worker_infos, msg_done);
+        for p in Vec::leak(workload.instantiate()) {
+            let pinfo = Box::leak(Box::new(PInfo::new()));
+            let (arrival, priority) = (p.arrival(), p.priority());
+
+            sim.execute_at(arrival.into(), p, msg_tx.clone(), pinfo, priority);
+        }
+        for p in make() {
+            sim.execute(
+                Box::leak(p),
+                msg_tx.clone(),
+                Box::leak(Box::new(PInfo::new())),
+            );
+        }
+        drop(msg_tx);
+
+        (sim.run(), share)
+    }
+
+    #[test]
+    fn arrivals_wait_as_new() {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name("priority").unwrap());
+        let workload = toml::from_str(
+            r#"
+            [[process]]
+            name = "early"
+            steps = [{ burst = "4ms" }, { exit = 0 }]
+
+            [[process]]
+            name = "late"
+            arrival = "10ms"
+            steps = [{ burst = "4ms" }, { exit = 0 }]
+            "#,
+        )
+        .unwrap();
+        let (report, share) = run(builder, 1, LockProtocol::None, workload, Vec::new);
+
+        let ms = Duration::from_millis;
+        let late = report.processes.iter().find(|p| p.name == "late").unwrap();
+        assert_eq!(late.arrival, ms(10));
+        assert_eq!(late.response, Some(ms(0)));
+        assert_eq!(late.finish, Some(ms(14)));
+
+        // the CPU idles from 4ms to 10ms
+        assert_eq!(report.makespan, ms(14));
+        let pinfo = share.sorted_pinfos().into_iter().find(|p| p.id == late.id);
+        assert_eq!(pinfo.unwrap().time_in(ProcState::New), ms(10));
+    }
+
+    #[test]
+    fn io_lanes() {
+        let builder = SchedulerBuilder::new().boxed_policy(policy::by_name("priority").unwrap());
+        let workload = toml::from_str(
+            r#"
+            [[process]]
+            name = "a"
+            repeat = 2
+            steps = [{ read = "txt/浓度.txt" }, { burst = 2 }, { read = "txt/白发.txt" }]
+
+            [[process]]
+            name = "b"
+            arrival = 20
+            steps = [{ read = "txt/浓度.txt" }]
+            "#,
+        )
+        .unwrap();
+        let (_, share) = run(builder, 2, LockProtocol::None, workload, Vec::new);
+
+        let ms = Duration::from_millis;
+        let mut io: Vec<_> = share
+            .timeline
+            .spans()
+            .iter()
+            .filter(|s| matches!(s.track, Track::Fs(_)))
+            .map(|s| (s.start, s.track))
+            .collect();
+        io.sort();
+
+        // the two copies of `a` read at once, then one after the other
+        assert_eq!(
+            io,
+            [
+                (ms(0), Track::Fs(0)),
+                (ms(0), Track::Fs(1)),
+                (ms(7), Track::Fs(0)),
+                (ms(7), Track::Fs(1)),
+                (ms(20), Track::Fs(0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Where the messages of the processes go.
+pub trait MsgSink: Send {
+    fn send(&mut self, msg: &str) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The messages lost so far, besides those `send` failed on.
+    fn dropped(&self) -> u64 {
+        0
+    }
+}
+
+/// Which `MsgSink` to open: `none`, `stdout`, `file:<path>`, `tcp:<addr>`
+/// (a line per message) or `tcp-len:<addr>` (length-prefixed).
+#[derive(Debug, Clone)]
+pub enum SinkSpec {
+    None,
+    Stdout,
+    File(PathBuf),
+    Tcp(String, Framing),
+}
+
+pub struct NullSink;
+
+pub struct StdoutSink;
+
+/// Appends a line per message, and rotates the file once it grows over
+/// `max_bytes`: `log` becomes `log.1`, `log.1` becomes `log.2`, and so on,
+/// keeping `keep` old files.
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+/// How messages are delimited on a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// A line per message, backslashes and newlines inside a message are
+    /// escaped as `\\` and `\n`.
+    Lines,
+    /// A big-endian `u32` length before each message.
+    LengthPrefixed,
+}
+
+/// Sends the messages over one TCP connection, reconnecting with an
+/// exponential backoff when it fails. Meanwhile messages wait in a backlog
+/// of up to `max_backlog`, past which the oldest are dropped.
+pub struct TcpSink {
+    addr: String,
+    framing: Framing,
+    stream: Option<TcpStream>,
+    backlog: VecDeque<String>,
+    max_backlog: usize,
+    retry_at: Instant,
+    backoff: Duration,
+    frame: Vec<u8>,
+    dropped: u64,
+}
+
+impl SinkSpec {
+    pub fn open(&self) -> io::Result<Box<dyn MsgSink>> {
+        Ok(match self {
+            SinkSpec::None => Box::new(NullSink),
+            SinkSpec::Stdout => Box::new(StdoutSink),
+            SinkSpec::File(path) => Box::new(FileSink::new(path.clone(), 1 << 20, 3)?),
+            SinkSpec::Tcp(addr, framing) => Box::new(TcpSink::new(addr.clone(), *framing, 10_000)),
+        })
+    }
+}
+
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            _ if s == "none" => Ok(SinkSpec::None),
+            _ if s == "stdout" => Ok(SinkSpec::Stdout),
+            Some(("file", path)) => Ok(SinkSpec::File(path.into())),
+            Some(("tcp", addr)) => Ok(SinkSpec::Tcp(addr.to_string(), Framing::Lines)),
+            Some(("tcp-len", addr)) => Ok(SinkSpec::Tcp(addr.to_string(), Framing::LengthPrefixed)),
+            _ => Err(format!(
+                "unknown sink {:?}, expected none, stdout, file:<path>, tcp:<addr> or tcp-len:<addr>",
+                s
+            )),
+        }
+    }
+}
+
+impl MsgSink for NullSink {
+    fn send(&mut self, _msg: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MsgSink for StdoutSink {
+    fn send(&mut self, msg: &str) -> io::Result<()> {
+        writeln!(io::stdout().lock(), "{}", msg)
+    }
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        match self.keep {
+            0 => fs::remove_file(&self.path)?,
+            _ => fs::rename(&self.path, self.rotated(1))?,
+        }
+
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl MsgSink for FileSink {
+    fn send(&mut self, msg: &str) -> io::Result<()> {
+        if self.written > 0 && self.written + msg.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", msg)?;
+        self.written += msg.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl TcpSink {
+    const MIN_BACKOFF: Duration = Duration::from_millis(50);
+    const MAX_BACKOFF: Duration = Duration::from_secs(5);
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+    /// A peer which stops reading must not stall the messages forever.
+    const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(addr: String, framing: Framing, max_backlog: usize) -> Self {
+        Self {
+            addr,
+            framing,
+            stream: None,
+            backlog: VecDeque::new(),
+            max_backlog,
+            retry_at: Instant::now(),
+            backoff: Self::MIN_BACKOFF,
+            frame: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        match self.open_stream() {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.backoff = Self::MIN_BACKOFF;
+                Ok(())
+            }
+            Err(e) => {
+                self.retry_at = Instant::now() + self.backoff;
+                self.backoff = (self.backoff * 2).min(Self::MAX_BACKOFF);
+                Err(e)
+            }
+        }
+    }
+
+    fn open_stream(&self) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "no address");
+
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, Self::CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(Self::WRITE_TIMEOUT))?;
+                    return Ok(stream);
+                }
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    fn write_frame(&mut self, msg: &str) -> io::Result<()> {
+        self.frame.clear();
+
+        match self.framing {
+            Framing::Lines => {
+                let escaped = msg.replace('\\', "\\\\").replace('\n', "\\n");
+                self.frame.extend_from_slice(escaped.as_bytes());
+                self.frame.push(b'\n');
+            }
+            Framing::LengthPrefixed => {
+                self.frame
+                    .extend_from_slice(&(msg.len() as u32).to_be_bytes());
+                self.frame.extend_from_slice(msg.as_bytes());
+            }
+        }
+
+        self.stream.as_mut().unwrap().write_all(&self.frame)
+    }
+
+    /// Write out the backlog, oldest first, as long as the connection holds.
+    fn drain(&mut self) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.connect()?;
+        }
+
+        while let Some(msg) = self.backlog.pop_front() {
+            if let Err(e) = self.write_frame(&msg) {
+                // it may or may not be through, better twice than never
+                self.backlog.push_front(msg);
+                self.stream = None;
+                self.retry_at = Instant::now() + self.backoff;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MsgSink for TcpSink {
+    fn send(&mut self, msg: &str) -> io::Result<()> {
+        self.backlog.push_back(msg.to_string());
+
+        if self.stream.is_some() || Instant::now() >= self.retry_at {
+            // a failure leaves the messages in the backlog, for the next try
+            let _ = self.drain();
+        }
+        while self.backlog.len() > self.max_backlog {
+            self.backlog.pop_front();
+            self.dropped += 1;
+        }
+        Ok(())
+    }
+
+    /// One last try to send the backlog, what is left of it is dropped.
+    fn flush(&mut self) -> io::Result<()> {
+        let res = self.drain();
+
+        self.dropped += self.backlog.len() as u64;
+        self.backlog.clear();
+        res
+    }
+
+    fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+
+    #[test]
+    fn tcp_sink_loopback() {
+        // nowhere to connect to for now
+        let mut sink = TcpSink::new("no port".to_string(), Framing::Lines, 3);
+
+        for msg in ["lost", "one", "two\nlines", "three\\n"] {
+            sink.send(msg).unwrap();
+        }
+        assert_eq!(sink.dropped(), 1);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        sink.addr = listener.local_addr().unwrap().to_string();
+        // the backoff is over
+        sink.retry_at = Instant::now();
+        sink.send("four").unwrap();
+        sink.send("five").unwrap();
+        sink.flush().unwrap();
+
+        // all of it on a single connection
+        let (stream, _) = listener.accept().unwrap();
+        drop(sink);
+        let lines: Vec<_> = BufReader::new(stream)
+            .lines()
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(lines, ["one", "two\\nlines", "three\\\\n", "four", "five"]);
+    }
+
+    #[test]
+    fn tcp_sink_bad_address_backs_off() {
+        let mut sink = TcpSink::new("no port".to_string(), Framing::LengthPrefixed, 10);
+
+        sink.send("one").unwrap();
+        assert!(sink.retry_at > Instant::now());
+        assert_eq!(sink.backoff, TcpSink::MIN_BACKOFF * 2);
+
+        // no new try before `retry_at`
+        sink.send("two").unwrap();
+        assert_eq!(sink.backoff, TcpSink::MIN_BACKOFF * 2);
+        assert_eq!(sink.backlog.len(), 2);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::clock;
+use crate::pcb::Pcb;
+use parking_lot::Mutex;
+use parking_lot::MutexGuard;
+use std::time::Duration;
+
+/// Where a span ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Track {
+    Worker(usize),
+    /// A file system thread.
+    Fs(usize),
+}
+
+/// Why a span ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanEnd {
+    /// The poll returned.
+    Yield,
+    /// The burst was cut at the end of the time slice.
+    Preempted,
+    /// The process blocked: on a file op, a child to wait for, a message,
+    /// or a mutex, semaphore or condvar.
+    Blocked,
+    /// The process went to sleep.
+    Sleep,
+    Exit,
+    FileRead,
+    FileWrite,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub track: Track,
+    pub pid: u32,
+    pub start: Duration,
+    pub end: Duration,
+    pub reason: SpanEnd,
+}
+
+/// What a mark is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkKind {
+    Priority,
+    Spawn,
+}
+
+/// Something which happened to a process at one point in time.
+#[derive(Debug, Clone)]
+pub struct Mark {
+    pub pid: u32,
+    pub at: Duration,
+    pub kind: MarkKind,
+    pub name: String,
+}
+
+/// Every span of every track, and the marks between them, the raw data of
+/// the charts and traces.
+pub struct Timeline {
+    spans: Mutex<Vec<Span>>,
+    marks: Mutex<Vec<Mark>>,
+}
+
+impl SpanEnd {
+    /// The end of a poll which left `pcb` ready to run.
+    pub fn of(pcb: &Pcb) -> Self {
+        match pcb.pending {
+            Some(_) => SpanEnd::Preempted,
+            None => SpanEnd::Yield,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SpanEnd::Yield => "yield",
+            SpanEnd::Preempted => "preempted",
+            SpanEnd::Blocked => "blocked",
+            SpanEnd::Sleep => "sleep",
+            SpanEnd::Exit => "exit",
+            SpanEnd::FileRead => "file read",
+            SpanEnd::FileWrite => "file write",
+        }
+    }
+}
+
+impl MarkKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MarkKind::Priority => "priority",
+            MarkKind::Spawn => "spawn",
+        }
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            spans: Mutex::new(Vec::new()),
+            marks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, track: Track, pid: u32, start: Duration, end: Duration, reason: SpanEnd) {
+        self.spans.lock().push(Span {
+            track,
+            pid,
+            start,
+            end,
+            reason,
+        });
+    }
+
+    pub fn mark(&self, pid: u32, kind: MarkKind, name: String) {
+        self.marks.lock().push(Mark {
+            pid,
+            at: clock::now(),
+            kind,
+            name,
+        });
+    }
+
+    pub fn spans(&self) -> MutexGuard<'_, Vec<Span>> {
+        self.spans.lock()
+    }
+
+    pub fn marks(&self) -> MutexGuard<'_, Vec<Mark>> {
+        self.marks.lock()
+    }
+}
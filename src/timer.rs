@@ -0,0 +1,99 @@
+use crate::clock;
+use parking_lot::Condvar;
+use parking_lot::Mutex;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Hands every item added to it to `fire` once `clock::now()` reaches its
+/// time, from a thread of its own. For real time only: the simulation keeps
+/// its own events.
+pub struct Timer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    queue: Mutex<(BinaryHeap<Entry<T>>, u64)>,
+    cond: Condvar,
+}
+
+struct Entry<T> {
+    at: Duration,
+    seq: u64,
+    item: T,
+}
+
+impl<T: Send + 'static> Timer<T> {
+    pub fn new(name: &str, mut fire: impl FnMut(T) + Send + 'static) -> Self {
+        let inner = Arc::new(Inner {
+            queue: Mutex::new((BinaryHeap::new(), 0)),
+            cond: Condvar::new(),
+        });
+        let shared = inner.clone();
+
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || loop {
+                let mut queue = shared.queue.lock();
+
+                let item = loop {
+                    let now = clock::now();
+
+                    match queue.0.peek() {
+                        Some(e) if e.at <= now => break queue.0.pop().unwrap().item,
+                        Some(e) => {
+                            let wait = e.at - now;
+                            shared.cond.wait_for(&mut queue, wait);
+                        }
+                        None => shared.cond.wait(&mut queue),
+                    }
+                };
+
+                drop(queue);
+                fire(item);
+            })
+            .unwrap();
+
+        Self { inner }
+    }
+
+    pub fn add(&self, at: Duration, item: T) {
+        let mut queue = self.inner.queue.lock();
+        let seq = queue.1;
+
+        queue.0.push(Entry { at, seq, item });
+        queue.1 += 1;
+        self.inner.cond.notify_one();
+    }
+}
+
+impl<T> Clone for Timer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    // reversed: the earliest item on the top of the heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
@@ -0,0 +1,135 @@
+use crate::scheduler::Share;
+use crate::timeline::Track;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+const WORKERS_PID: u32 = 1;
+const IO_PID: u32 = 2;
+
+/// Write `share.timeline` to `path` in the Chrome Trace Event format, to
+/// open in chrome://tracing or Perfetto.
+pub fn export(share: &Share, path: &Path) -> io::Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+
+    serde_json::to_writer(w, &trace(share)).map_err(io::Error::from)
+}
+
+/// Each worker is a thread of the "Workers" process, each file system
+/// thread one of the "I/O" process, every poll and file op a slice on them.
+/// Priority changes and spawns are global instant events.
+pub fn trace(share: &Share) -> Value {
+    let spans = share.timeline.spans();
+    let marks = share.timeline.marks();
+    let tracks: BTreeSet<Track> = spans.iter().map(|s| s.track).collect();
+    let name = |pid: u32| {
+        share
+            .pinfo(pid)
+            .and_then(|p| p.name)
+            .map_or_else(|| format!("#{}", pid), |n| n.clone())
+    };
+
+    let mut events = vec![
+        json!({"name": "process_name", "ph": "M", "pid": WORKERS_PID, "args": {"name": "Workers"}}),
+        json!({"name": "process_name", "ph": "M", "pid": IO_PID, "args": {"name": "I/O"}}),
+    ];
+
+    for track in &tracks {
+        let (pid, tid) = ids(*track);
+        let thread = match track {
+            Track::Worker(id) => format!("worker {}", id),
+            Track::Fs(id) => format!("fs {}", id),
+        };
+
+        events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": pid,
+            "tid": tid,
+            "args": {"name": thread},
+        }));
+    }
+
+    for span in spans.iter() {
+        let (pid, tid) = ids(span.track);
+
+        events.push(json!({
+            "name": name(span.pid),
+            "cat": match span.track {
+                Track::Worker(_) => "poll",
+                Track::Fs(_) => "io",
+            },
+            "ph": "X",
+            "ts": micros(span.start),
+            "dur": micros(span.end - span.start),
+            "pid": pid,
+            "tid": tid,
+            "args": {"pid": span.pid, "end": span.reason.as_str()},
+        }));
+    }
+
+    for mark in marks.iter() {
+        events.push(json!({
+            "name": format!("{}: {}", name(mark.pid), mark.name),
+            "cat": mark.kind.as_str(),
+            "ph": "i",
+            "s": "g",
+            "ts": micros(mark.at),
+            "pid": WORKERS_PID,
+            "tid": 0,
+            "args": {"pid": mark.pid},
+        }));
+    }
+
+    json!({"traceEvents": events, "displayTimeUnit": "ms"})
+}
+
+fn ids(track: Track) -> (u32, usize) {
+    match track {
+        Track::Worker(id) => (WORKERS_PID, id),
+        Track::Fs(id) => (IO_PID, id),
+    }
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::MarkKind;
+
+    #[test]
+    fn mark_categories() {
+        let share = Share::new();
+        share
+            .timeline
+            .mark(1, MarkKind::Spawn, "spawn 2".to_string());
+        share
+            .timeline
+            .mark(2, MarkKind::Priority, "priority 0 => 3".to_string());
+
+        let trace = trace(&share);
+        let marks: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|e| e["ph"] == "i")
+            .map(|e| (e["cat"].as_str().unwrap(), e["name"].as_str().unwrap()))
+            .collect();
+
+        assert_eq!(
+            marks,
+            [
+                ("spawn", "#1: spawn 2"),
+                ("priority", "#2: priority 0 => 3")
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! The `'static` references the scheduler threads share, and small helpers
+//! of the printer.
+
+use std::time::Duration;
+use std::time::Instant;
+
+pub mod convert {
+    /// A `&mut` to what `t` points to.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may access it meanwhile, as for the process a worker
+    /// polls, or the caller must put up with racy reads, as for the printer.
+    #[allow(invalid_reference_casting, clippy::mut_from_ref)]
+    pub unsafe fn make_mut<T: ?Sized>(t: &T) -> &mut T {
+        &mut *(t as *const T as *mut T)
+    }
+
+    /// # Safety
+    ///
+    /// As `make_mut`, and `t` must live as long as the program.
+    #[allow(invalid_reference_casting, clippy::mut_from_ref)]
+    pub unsafe fn static_ref_mut<T: ?Sized>(t: &T) -> &'static mut T {
+        &mut *(t as *const T as *mut T)
+    }
+
+    /// Extend a reference to `'static`.
+    pub trait StaticRef {
+        type Target: ?Sized + 'static;
+
+        /// # Safety
+        ///
+        /// What it points to must live as long as the program.
+        unsafe fn static_ref(self) -> &'static Self::Target;
+
+        /// # Safety
+        ///
+        /// As `static_ref` and `make_mut`.
+        unsafe fn static_ref_mut(self) -> &'static mut Self::Target;
+    }
+
+    impl<T: ?Sized + 'static> StaticRef for &T {
+        type Target = T;
+
+        unsafe fn static_ref(self) -> &'static T {
+            &*(self as *const T)
+        }
+
+        unsafe fn static_ref_mut(self) -> &'static mut T {
+            static_ref_mut(self)
+        }
+    }
+
+    impl<T: ?Sized + 'static> StaticRef for &mut T {
+        type Target = T;
+
+        unsafe fn static_ref(self) -> &'static T {
+            &*(self as *const T)
+        }
+
+        unsafe fn static_ref_mut(self) -> &'static mut T {
+            &mut *(self as *mut T)
+        }
+    }
+
+    /// `n` values leaked for the rest of the run.
+    pub struct StaticRefArray<T: 'static>(&'static [T]);
+
+    impl<T> StaticRefArray<T> {
+        pub fn new(n: usize, f: impl FnMut() -> T) -> Self {
+            Self(Vec::leak(std::iter::repeat_with(f).take(n).collect()))
+        }
+
+        pub fn iter(&self) -> std::slice::Iter<'static, T> {
+            self.0.iter()
+        }
+    }
+}
+
+pub mod types {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    pub type SStr = Cow<'static, str>;
+    pub type SPath = Cow<'static, Path>;
+
+    /// A path, or a string to use as one.
+    #[derive(Debug, Clone)]
+    pub enum SS {
+        SStr(SStr),
+        SPath(SPath),
+    }
+
+    impl AsRef<Path> for SS {
+        fn as_ref(&self) -> &Path {
+            match self {
+                SS::SStr(s) => Path::new(s.as_ref()),
+                SS::SPath(p) => p,
+            }
+        }
+    }
+}
+
+pub mod rate {
+    use std::fmt;
+
+    /// `.0` out of `.1`, as a percentage with `.2` decimals.
+    pub struct Rate(pub f32, pub f32, pub usize);
+
+    /// What is left of `.1` after `.0`, as a percentage with `.2` decimals.
+    pub struct RemainRate(pub f64, pub f64, pub usize);
+
+    impl fmt::Display for Rate {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:.*}%", self.2, self.0 / self.1 * 100.0)
+        }
+    }
+
+    impl fmt::Display for RemainRate {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:.*}%", self.2, (1.0 - self.0 / self.1) * 100.0)
+        }
+    }
+}
+
+/// Leak each value for the rest of the run, as `&'static`.
+#[macro_export]
+macro_rules! static_refs {
+	($($name:ident = $e:expr;)*) => {
+		$(let $name: &'static _ = Box::leak(Box::new($e));)*
+	};
+}
+
+/// Leak each value for the rest of the run, as `&'static mut`.
+#[macro_export]
+macro_rules! static_refs_mut {
+	($($name:ident = $e:expr;)*) => {
+		$(let $name: &'static mut _ = Box::leak(Box::new($e));)*
+	};
+}
+
+/// How long `f` took, in wall-clock time.
+pub fn time_test<R>(f: impl FnOnce() -> R) -> (Duration, R) {
+    let start = Instant::now();
+    let r = f();
+
+    (start.elapsed(), r)
+}
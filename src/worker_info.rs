@@ -1,10 +1,10 @@
 use std::time::Duration;
-use std::time::Instant;
 
 #[derive(Debug)]
 pub struct WorkerInfo {
     pub id: usize,
-    pub start_point: Instant,
+    /// The `clock::now()` when the worker started.
+    pub start_point: Duration,
     pub waiting_time: Duration,
     pub idle: bool,
 }
@@ -0,0 +1,575 @@
+use crate::clock;
+use crate::event::MsgTx;
+use crate::event::SimEvent;
+use crate::fs::FileOp;
+use crate::import;
+use crate::ops::Op;
+use crate::ops::OpsRes;
+use crate::pcb::PollRes;
+use crate::pcb::Process;
+use crate::util::types::SS;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The processes of a run, read from a TOML or JSON file:
+///
+/// ```toml
+/// random = 5
+///
+/// [[process]]
+/// name = "editor"
+/// arrival = "100ms"
+/// priority = 2
+/// repeat = 3
+/// loops = 10
+/// steps = [
+///     { burst = "20ms" },
+///     { read = "txt/a.txt" },
+///     { burst = 15 },
+///     { add_priority = 1 },
+/// ]
+/// ```
+///
+/// Durations are strings with a unit (`ns`, `us`, `ms`, `s`), or numbers of
+/// milliseconds.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workload {
+    /// How many random processes run besides the listed ones.
+    #[serde(default)]
+    pub random: usize,
+    #[serde(default, rename = "process")]
+    pub processes: Vec<ProcessSpec>,
+    /// The ceilings of the mutexes, for `--lock-protocol=ceiling`.
+    #[serde(default, rename = "mutex")]
+    pub mutexes: Vec<MutexSpec>,
+    /// The semaphores not starting at 0.
+    #[serde(default, rename = "semaphore")]
+    pub semaphores: Vec<SemSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MutexSpec {
+    pub id: u32,
+    pub ceiling: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SemSpec {
+    pub id: u32,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessSpec {
+    pub name: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, deserialize_with = "duration")]
+    pub arrival: Duration,
+    /// Copies of the process, all arriving together.
+    #[serde(default = "one")]
+    pub repeat: u32,
+    /// How many times each copy runs through `steps`.
+    #[serde(default = "one")]
+    pub loops: u32,
+    #[serde(default, deserialize_with = "opt_duration")]
+    pub period: Option<Duration>,
+    #[serde(default, deserialize_with = "opt_duration")]
+    pub wcet: Option<Duration>,
+    #[serde(default, deserialize_with = "opt_duration")]
+    pub deadline: Option<Duration>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Work on the CPU for that long.
+    Burst(#[serde(deserialize_with = "duration")] Duration),
+    Read(PathBuf),
+    Write {
+        path: String,
+        #[serde(default)]
+        content: String,
+    },
+    AddPriority(i32),
+    SubPriority(i32),
+    SetPriority(i32),
+    TransferTickets {
+        to: u32,
+        tickets: u32,
+    },
+    Yield,
+    Sleep(#[serde(deserialize_with = "duration")] Duration),
+    Exit(i32),
+    /// Start a child process running its own steps.
+    Spawn(Box<ProcessSpec>),
+    /// Wait for the child spawned last.
+    Wait,
+    WaitAny,
+    Send {
+        to: Peer,
+        #[serde(default)]
+        payload: String,
+    },
+    Recv,
+    Lock(u32),
+    Unlock(u32),
+    SemWait(u32),
+    SemPost(u32),
+    CondWait {
+        cond: u32,
+        lock: u32,
+    },
+    CondSignal(u32),
+    CondBroadcast(u32),
+}
+
+/// Who a `Step::Send` goes to.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum Peer {
+    Id(u32),
+    Named(PeerName),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerName {
+    /// The child spawned last.
+    Child,
+    /// The sender of the message received last.
+    Sender,
+}
+
+/// Runs the steps of a `ProcessSpec`: each poll does one burst, and returns
+/// the op which follows it, if any.
+#[derive(Debug)]
+pub struct ScriptProcess {
+    name: String,
+    spec: ProcessSpec,
+    pc: usize,
+    loops: u32,
+    buf: String,
+    /// The child spawned last, for `Step::Wait`.
+    child: Option<u32>,
+    /// The sender of the message received last.
+    sender: Option<u32>,
+}
+
+impl Workload {
+    /// Read a workload, by the extension of `path`: JSON, a SWF trace, a
+    /// Google cluster style CSV trace, or TOML.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|e| invalid(e.to_string())),
+            Some("swf") => import::swf(&text),
+            Some("csv") => import::google(&text),
+            _ => toml::from_str(&text).map_err(|e| invalid(e.to_string())),
+        }
+    }
+
+    /// Multiply every arrival time and burst by `factor`, to replay traces
+    /// of hours in seconds. Fails if a duration overflows.
+    pub fn scale(&mut self, factor: f64) -> Result<(), String> {
+        for spec in &mut self.processes {
+            spec.scale(factor)?;
+        }
+        Ok(())
+    }
+
+    /// A process per copy of every listed process, by arrival time.
+    pub fn instantiate(&self) -> Vec<ScriptProcess> {
+        let mut ps = Vec::new();
+
+        for spec in &self.processes {
+            for i in 0..spec.repeat {
+                let name = match spec.repeat {
+                    1 => spec.name.clone(),
+                    _ => format!("{}#{}", spec.name, i + 1),
+                };
+                ps.push(ScriptProcess::new(name, spec.clone()));
+            }
+        }
+        ps.sort_by_key(|p| p.spec.arrival);
+        ps
+    }
+}
+
+impl ProcessSpec {
+    fn scale(&mut self, factor: f64) -> Result<(), String> {
+        let scale = |d: &mut Duration| -> Result<(), String> {
+            *d = secs(d.as_secs_f64() * factor)?;
+            Ok(())
+        };
+
+        scale(&mut self.arrival)?;
+        for d in [&mut self.period, &mut self.wcet, &mut self.deadline] {
+            d.as_mut().map(scale).transpose()?;
+        }
+        for step in &mut self.steps {
+            match step {
+                Step::Burst(d) | Step::Sleep(d) => scale(d)?,
+                Step::Spawn(spec) => spec.scale(factor)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    /// `child` is the one spawned last, `sender` the one received from last.
+    /// Fails on a send to either when there is none.
+    fn into_op(self, child: Option<u32>, sender: Option<u32>) -> Result<Op, String> {
+        let op = match self {
+            Step::Burst(_) => Op::None,
+            Step::Read(path) => Op::FileOp(FileOp::Read(SS::SPath(Cow::Owned(path)))),
+            Step::Write { path, content } => Op::FileOp(FileOp::Write {
+                path,
+                content: Cow::Owned(content),
+            }),
+            Step::AddPriority(p) => Op::AddPriority(p),
+            Step::SubPriority(p) => Op::SubPriority(p),
+            Step::SetPriority(p) => Op::SetPriority(p),
+            Step::TransferTickets { to, tickets } => Op::TransferTickets { to, tickets },
+            Step::Yield => Op::Yield,
+            Step::Sleep(d) => Op::Sleep(d),
+            Step::Exit(code) => Op::Exit(code),
+            Step::Spawn(spec) => Op::Spawn(Box::new(ScriptProcess::new(spec.name.clone(), *spec))),
+            Step::Wait => child.map_or(Op::WaitAny, Op::Wait),
+            Step::WaitAny => Op::WaitAny,
+            Step::Send { to, payload } => {
+                let to = match to {
+                    Peer::Id(id) => id,
+                    Peer::Named(PeerName::Child) => child.ok_or("no child to send to")?,
+                    Peer::Named(PeerName::Sender) => sender.ok_or("no sender to send to")?,
+                };
+                Op::Send { to, payload }
+            }
+            Step::Recv => Op::Recv,
+            Step::Lock(id) => Op::Lock(id),
+            Step::Unlock(id) => Op::Unlock(id),
+            Step::SemWait(id) => Op::SemWait(id),
+            Step::SemPost(id) => Op::SemPost(id),
+            Step::CondWait { cond, lock } => Op::CondWait { cond, lock },
+            Step::CondSignal(cond) => Op::CondSignal(cond),
+            Step::CondBroadcast(cond) => Op::CondBroadcast(cond),
+        };
+        Ok(op)
+    }
+}
+
+impl ScriptProcess {
+    pub fn new(name: String, spec: ProcessSpec) -> Self {
+        Self {
+            name,
+            pc: 0,
+            loops: 0,
+            spec,
+            buf: String::new(),
+            child: None,
+            sender: None,
+        }
+    }
+
+    pub fn arrival(&self) -> Duration {
+        self.spec.arrival
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.spec.priority
+    }
+
+    fn next_step(&mut self) -> Option<Step> {
+        if self.pc == self.spec.steps.len() {
+            self.pc = 0;
+            self.loops += 1;
+        }
+        if self.loops >= self.spec.loops || self.spec.steps.is_empty() {
+            return None;
+        }
+
+        self.pc += 1;
+        Some(self.spec.steps[self.pc - 1].clone())
+    }
+
+    fn peek_step(&self) -> Option<&Step> {
+        match self.spec.steps.get(self.pc) {
+            Some(step) => Some(step),
+            None if self.loops + 1 < self.spec.loops => self.spec.steps.first(),
+            None => None,
+        }
+    }
+}
+
+impl Process for ScriptProcess {
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    fn poll(&mut self, msg_tx: MsgTx, ops_res: OpsRes) -> PollRes {
+        let err = match ops_res {
+            OpsRes::Spawned(child) => {
+                self.child = Some(child);
+                None
+            }
+            OpsRes::Received(msg) => {
+                self.sender = Some(msg.from);
+                let (from, payload) = (msg.from, msg.payload);
+                msg_tx.send(&self.name, SimEvent::Received { from, payload });
+                None
+            }
+            OpsRes::Exited { child, code } => {
+                msg_tx.send(&self.name, SimEvent::Reaped { child, code });
+                None
+            }
+            OpsRes::NoChild => Some("no child to wait for".to_string()),
+            OpsRes::NoMessage => Some("no message can come".to_string()),
+            OpsRes::NoProcess(to) => Some(format!("no process {} to send to", to)),
+            OpsRes::Deadlock(id) => Some(format!("mutex {} is held already", id)),
+            OpsRes::NotOwner(id) => Some(format!("mutex {} is not held", id)),
+            _ => None,
+        };
+        if let Some(err) = err {
+            msg_tx.send(&self.name, SimEvent::OpErr { err });
+        }
+
+        let step = match self.next_step() {
+            Some(Step::Burst(d)) => {
+                clock::burn(d);
+
+                match self.peek_step() {
+                    Some(Step::Burst(_)) | None => return PollRes::Polling(Op::None),
+                    Some(_) => self.next_step().unwrap(),
+                }
+            }
+            Some(step) => step,
+            None => return PollRes::Ready,
+        };
+
+        match step.into_op(self.child, self.sender) {
+            Ok(op) => PollRes::Polling(op),
+            Err(err) => {
+                msg_tx.send(&self.name, SimEvent::OpErr { err });
+                PollRes::Polling(Op::None)
+            }
+        }
+    }
+
+    fn file_buf(&mut self) -> Option<&mut String> {
+        Some(&mut self.buf)
+    }
+
+    fn period(&self) -> Option<Duration> {
+        self.spec.period
+    }
+
+    fn wcet(&self) -> Option<Duration> {
+        self.spec.wcet
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        self.spec.deadline.or(self.spec.period)
+    }
+}
+
+/// Parse a duration such as `1.5s`, `20ms`, `300us` or `10ns`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: f64 = n
+        .parse()
+        .map_err(|_| format!("invalid duration: {:?}", s))?;
+    let secs = match unit.trim() {
+        "ns" => n / 1e9,
+        "us" | "µs" => n / 1e6,
+        "" | "ms" => n / 1e3,
+        "s" => n,
+        "m" | "min" => n * 60.0,
+        _ => return Err(format!("invalid duration unit: {:?}", s)),
+    };
+
+    self::secs(secs).map_err(|e| format!("{}: {:?}", e, s))
+}
+
+/// A duration of `secs` seconds, unless negative, not a number, or too long.
+pub fn secs(secs: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid duration: {}s", secs))
+}
+
+/// A `--time-scale` factor: a positive finite number.
+pub fn parse_scale(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f.is_finite() && f > 0.0 => Ok(f),
+        _ => Err(format!(
+            "invalid time scale: {:?}, expected a positive number",
+            s
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDuration {
+    Millis(f64),
+    Text(String),
+}
+
+fn duration<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    match RawDuration::deserialize(d)? {
+        RawDuration::Millis(ms) => secs(ms / 1e3).map_err(de::Error::custom),
+        RawDuration::Text(s) => parse_duration(&s).map_err(de::Error::custom),
+    }
+}
+
+fn opt_duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    duration(d).map(Some)
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1.5s"), Ok(ms(1500)));
+        assert_eq!(parse_duration("20ms"), Ok(ms(20)));
+        assert_eq!(parse_duration("20"), Ok(ms(20)));
+        assert_eq!(parse_duration("300us"), Ok(Duration::from_micros(300)));
+        assert_eq!(parse_duration("10ns"), Ok(Duration::from_nanos(10)));
+        assert_eq!(parse_duration("2min"), Ok(Duration::from_secs(120)));
+
+        assert!(parse_duration("-1ms").is_err());
+        assert!(parse_duration("1h").is_err());
+        assert!(parse_duration("1e3ms").is_err());
+        assert!(parse_duration("99999999999999999999999s").is_err());
+    }
+
+    #[test]
+    fn steps() {
+        let w: Workload = toml::from_str(
+            r#"
+            [[process]]
+            name = "a"
+            arrival = "1s"
+            period = 50
+            steps = [{ burst = "20ms" }, { sleep = 5 }, "yield", { lock = 1 }, { exit = 0 }]
+            "#,
+        )
+        .unwrap();
+        let spec = &w.processes[0];
+
+        assert_eq!(spec.arrival, ms(1000));
+        assert_eq!(spec.period, Some(ms(50)));
+        assert_eq!((spec.repeat, spec.loops), (1, 1));
+        assert!(matches!(
+            spec.steps[..],
+            [
+                Step::Burst(b),
+                Step::Sleep(s),
+                Step::Yield,
+                Step::Lock(1),
+                Step::Exit(0)
+            ] if b == ms(20) && s == ms(5)
+        ));
+    }
+
+    #[test]
+    fn send_to_no_peer() {
+        let w: Workload = toml::from_str(
+            r#"
+            [[process]]
+            name = "a"
+            steps = [{ send = { to = "child" } }, { send = { to = "sender" } }]
+            "#,
+        )
+        .unwrap();
+        let mut p = ScriptProcess::new("a".to_string(), w.processes[0].clone());
+        let (tx, rx) = crossbeam_channel::unbounded();
+
+        for _ in 0..2 {
+            let res = p.poll(MsgTx::new(tx.clone(), 1, None), OpsRes::Empty);
+            assert!(matches!(res, PollRes::Polling(Op::None)));
+        }
+        let errs: Vec<_> = rx
+            .try_iter()
+            .map(|msg| match msg.event {
+                SimEvent::OpErr { err } => err,
+                e => panic!("{:?}", e),
+            })
+            .collect();
+
+        assert_eq!(errs, ["no child to send to", "no sender to send to"]);
+    }
+
+    #[test]
+    fn bad_durations() {
+        let burst = |burst: &str| {
+            let text = format!(
+                "[[process]]\nname = \"a\"\nsteps = [{{ burst = {} }}]",
+                burst
+            );
+            toml::from_str::<Workload>(&text)
+        };
+
+        assert!(burst("10").is_ok());
+        assert!(burst("-10").is_err());
+        assert!(burst("inf").is_err());
+        assert!(burst("nan").is_err());
+        assert!(burst("1e30").is_err());
+        assert!(burst("\"-10ms\"").is_err());
+    }
+
+    #[test]
+    fn scales() {
+        assert_eq!(parse_scale("0.5"), Ok(0.5));
+        for s in ["0", "-1", "NaN", "inf", "x"] {
+            assert!(parse_scale(s).is_err(), "{}", s);
+        }
+
+        let mut w: Workload = toml::from_str(
+            r#"
+            [[process]]
+            name = "a"
+            arrival = "1s"
+            steps = [{ burst = "20ms" }, { spawn = { name = "b", steps = [{ sleep = 4 }] } }]
+            "#,
+        )
+        .unwrap();
+
+        w.scale(0.5).unwrap();
+        let spec = &w.processes[0];
+        assert_eq!(spec.arrival, ms(500));
+        assert!(matches!(spec.steps[0], Step::Burst(b) if b == ms(10)));
+        assert!(matches!(&spec.steps[1], Step::Spawn(child)
+            if matches!(child.steps[0], Step::Sleep(s) if s == ms(2))));
+
+        assert!(w.scale(1e300).is_err());
+    }
+}